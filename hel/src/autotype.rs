@@ -0,0 +1,53 @@
+use crate::clipboard::which;
+use std::env;
+
+/// The tool that types its stdin into the focused window: wtype or ydotool on Wayland, xdotool on
+/// X11 and ydotool elsewhere, since it works through uinput.
+pub fn detect() -> Option<(String, Vec<String>)> {
+    find(|v| env::var(v).ok(), which)
+}
+
+pub fn find(env: impl Fn(&str) -> Option<String>, which: impl Fn(&str) -> bool) -> Option<(String, Vec<String>)> {
+    let set = |v: &str| env(v).map(|v| !v.is_empty()).unwrap_or(false);
+    let tool = |cmd: &str, args: &[&str]| Some((cmd.to_string(), args.iter().map(|a| a.to_string()).collect()));
+    match () {
+        _ if set("WAYLAND_DISPLAY") && which("wtype") => tool("wtype", &["-"]),
+        _ if set("DISPLAY") && !set("WAYLAND_DISPLAY") && which("xdotool") => {
+            tool("xdotool", &["type", "--clearmodifiers", "--file", "-"])
+        }
+        _ if which("ydotool") => tool("ydotool", &["type", "--file", "-"]),
+        _ => None,
+    }
+}
+
+/// The user name kept in a comment as a `user=NAME` word.
+pub fn username(comment: &str) -> Option<String> {
+    comment
+        .split_whitespace()
+        .find_map(|w| w.strip_prefix("user="))
+        .filter(|u| !u.is_empty())
+        .map(|u| u.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn autotype_test() {
+        let env = |vars: &'static [&'static str]| move |v: &str| vars.contains(&v).then(|| "1".to_string());
+        let tools = |tools: &'static [&'static str]| move |c: &str| tools.contains(&c);
+        assert_eq!(find(env(&["WAYLAND_DISPLAY"]), tools(&["wtype", "ydotool"])).unwrap().0, "wtype");
+        assert_eq!(find(env(&["WAYLAND_DISPLAY", "DISPLAY"]), tools(&["xdotool", "ydotool"])).unwrap().0, "ydotool");
+        assert_eq!(
+            find(env(&["DISPLAY"]), tools(&["xdotool"])),
+            Some((
+                "xdotool".to_string(),
+                vec!["type", "--clearmodifiers", "--file", "-"].into_iter().map(|a| a.to_string()).collect()
+            ))
+        );
+        assert_eq!(find(env(&[]), tools(&["xdotool"])), None);
+        assert_eq!(username("work account user=alice@example.com"), Some("alice@example.com".to_string()));
+        assert_eq!(username("user= nothing"), None);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::config::Config;
+use crate::structs::LKErr;
+use crate::utils::{call_cmd_with_input, get_cmd_args_from_command};
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// How a clipboard gets its value: a command reading it from stdin, or an OSC 52 escape sequence
+/// written to the terminal, which also works over SSH.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Copy {
+    Command(String, Vec<String>),
+    Osc52 { selection: char, wrap: Wrap },
+}
+
+/// Terminal multiplexers only pass escape sequences through to the outer terminal when wrapped.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Wrap {
+    None,
+    Tmux,
+    Screen,
+}
+
+/// A clipboard tool: how to copy, and the shell commands to read the clipboard back and to clear
+/// it, when the tool has them.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Clipboard {
+    pub name: String,
+    pub copy: Copy,
+    pub paste: Option<String>,
+    pub clear: Option<String>,
+}
+
+impl Clipboard {
+    fn new(copy: &str, paste: Option<&str>, clear: Option<&str>) -> Result<Self, LKErr> {
+        let (cmd, args) = get_cmd_args_from_command(copy)
+            .map_err(|e| LKErr::Config(format!("failed to parse the pb command {:?}: {}", copy, e)))?;
+        Ok(Self {
+            name: cmd.to_string(),
+            copy: Copy::Command(cmd, args),
+            paste: paste.map(|p| p.to_string()),
+            clear: clear.map(|c| c.to_string()),
+        })
+    }
+
+    fn osc52(primary: bool, wrap: Wrap) -> Self {
+        Self {
+            name: "OSC 52".to_string(),
+            copy: Copy::Osc52 {
+                selection: if primary { 'p' } else { 'c' },
+                wrap,
+            },
+            paste: None,
+            clear: None,
+        }
+    }
+
+    /// The clipboard for this session, `pb` from the config wins over the detected tools. Set it to
+    /// `osc52` to copy through the terminal, which is also picked in SSH sessions without a display.
+    /// A `pb` that is not a command fails instead of falling back, the value would end up on screen.
+    pub fn detect(config: &Config, primary: bool) -> Result<Self, LKErr> {
+        Self::find(config.get("pb"), primary, |v| env::var(v).ok(), which)
+    }
+
+    pub fn find(
+        pb: Option<&str>,
+        primary: bool,
+        env: impl Fn(&str) -> Option<String>,
+        which: impl Fn(&str) -> bool,
+    ) -> Result<Self, LKErr> {
+        let set = |v: &str| env(v).map(|v| !v.is_empty()).unwrap_or(false);
+        let (xclip, xsel, wl) = match primary {
+            true => ("primary", "--primary", " --primary"),
+            false => ("clipboard", "--clipboard", ""),
+        };
+        let wrap = match () {
+            _ if set("TMUX") => Wrap::Tmux,
+            _ if set("STY") => Wrap::Screen,
+            _ => Wrap::None,
+        };
+        match pb {
+            Some("osc52") => Ok(Self::osc52(primary, wrap)),
+            Some(pb) => Self::new(pb, None, None),
+            None if set("WAYLAND_DISPLAY") && which("wl-copy") => Self::new(
+                &format!("wl-copy{}", wl),
+                Some(&format!("wl-paste --no-newline{}", wl)),
+                Some(&format!("wl-copy --clear{}", wl)),
+            ),
+            None if set("DISPLAY") && which("xclip") => Self::new(
+                &format!("xclip -selection {}", xclip),
+                Some(&format!("xclip -selection {} -o", xclip)),
+                Some(&format!("printf '' | xclip -selection {}", xclip)),
+            ),
+            None if set("DISPLAY") && which("xsel") => Self::new(
+                &format!("xsel {} --input", xsel),
+                Some(&format!("xsel {} --output", xsel)),
+                Some(&format!("xsel {} --clear", xsel)),
+            ),
+            None if set("SSH_TTY") || set("SSH_CONNECTION") => Ok(Self::osc52(primary, wrap)),
+            None if set("TMUX") => {
+                Self::new("tmux load-buffer -", Some("tmux save-buffer -"), Some("tmux delete-buffer"))
+            }
+            None if env::consts::OS == "macos" => Self::new("pbcopy", Some("pbpaste"), Some("printf '' | pbcopy")),
+            None if env::consts::OS == "linux" => Self::new("xclip", None, None),
+            None => Self::new("cat", None, None),
+        }
+    }
+
+    /// Copies `value`, returns what the copy command printed.
+    pub fn copy(&self, value: &str) -> io::Result<String> {
+        match &self.copy {
+            Copy::Command(cmd, args) => call_cmd_with_input(cmd, args, value),
+            Copy::Osc52 { selection, wrap } => {
+                OpenOptions::new()
+                    .write(true)
+                    .open("/dev/tty")?
+                    .write_all(osc52(*selection, *wrap, value).as_bytes())?;
+                Ok("".to_string())
+            }
+        }
+    }
+
+    /// Clears the clipboard after `seconds` in the background, unless it was changed in the
+    /// meantime. Returns false when the tool cannot read the clipboard back. The terminal cannot
+    /// be asked for its clipboard, so OSC 52 always sends the clear sequence.
+    pub fn clear_after(&self, value: &str, seconds: u32) -> io::Result<bool> {
+        // the value goes through stdin so it does not show up in the process list
+        let (script, value) = match (&self.copy, &self.paste, &self.clear) {
+            _ if seconds == 0 => return Ok(false),
+            (Copy::Osc52 { selection, wrap }, _, _) => {
+                (format!("v=$(cat); sleep {}; printf '%s' \"$v\" >/dev/tty", seconds), osc52(*selection, *wrap, ""))
+            }
+            (_, Some(paste), Some(clear)) => (
+                format!("v=$(cat); sleep {}; [ \"$({})\" = \"$v\" ] && {{ {}; }}", seconds, paste, clear),
+                value.to_string(),
+            ),
+            _ => return Ok(false),
+        };
+        let mut child = Command::new("sh")
+            .args(["-c", &script])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        child.stdin.take().unwrap().write_all(value.as_bytes())?;
+        Ok(true)
+    }
+}
+
+/// The OSC 52 sequence setting `selection` to `value`, an empty value clears it.
+pub fn osc52(selection: char, wrap: Wrap, value: &str) -> String {
+    let seq = format!("\x1b]52;{};{}\x07", selection, base64::encode(value));
+    match wrap {
+        Wrap::None => seq,
+        Wrap::Tmux => format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b")),
+        Wrap::Screen => format!("\x1bP{}\x1b\\", seq),
+    }
+}
+
+/// Whether `cmd` is an executable in `$PATH`.
+pub fn which(cmd: &str) -> bool {
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| is_executable(&dir.join(cmd))))
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipboard_find_test() {
+        let env = |vars: &'static [&'static str]| move |v: &str| vars.contains(&v).then(|| "1".to_string());
+        let all = |_: &str| true;
+        let wl = Clipboard::find(None, false, env(&["WAYLAND_DISPLAY", "DISPLAY"]), all).unwrap();
+        assert_eq!(wl.copy, Copy::Command("wl-copy".to_string(), vec![]));
+        assert_eq!(wl.clear.as_deref(), Some("wl-copy --clear"));
+        let x = Clipboard::find(None, true, env(&["DISPLAY"]), all).unwrap();
+        assert_eq!(x.name, "xclip");
+        assert_eq!(x.paste.as_deref(), Some("xclip -selection primary -o"));
+        let xsel = Clipboard::find(None, false, env(&["DISPLAY"]), |c| c == "xsel").unwrap();
+        assert_eq!(
+            xsel.copy,
+            Copy::Command("xsel".to_string(), vec!["--clipboard".to_string(), "--input".to_string()])
+        );
+        let custom = Clipboard::find(Some("my-copy -q"), false, env(&["DISPLAY"]), all).unwrap();
+        assert_eq!(custom.copy, Copy::Command("my-copy".to_string(), vec!["-q".to_string()]));
+        assert!(!custom.clear_after("secret", 1).unwrap());
+        let broken = Clipboard::find(Some("my-copy \"-q"), false, env(&["DISPLAY"]), all);
+        assert_eq!(broken.unwrap_err().exit_code(), 7);
+        let ssh = Clipboard::find(None, false, env(&["SSH_TTY", "TMUX"]), all).unwrap();
+        assert_eq!(
+            ssh.copy,
+            Copy::Osc52 {
+                selection: 'c',
+                wrap: Wrap::Tmux
+            }
+        );
+        let osc = Clipboard::find(Some("osc52"), true, env(&["DISPLAY", "STY"]), all).unwrap();
+        assert_eq!(
+            osc.copy,
+            Copy::Osc52 {
+                selection: 'p',
+                wrap: Wrap::Screen
+            }
+        );
+    }
+
+    #[test]
+    fn osc52_test() {
+        assert_eq!(osc52('c', Wrap::None, "secret"), "\x1b]52;c;c2VjcmV0\x07");
+        assert_eq!(osc52('c', Wrap::None, ""), "\x1b]52;c;\x07");
+        assert_eq!(osc52('p', Wrap::Tmux, "secret"), "\x1bPtmux;\x1b\x1b]52;p;c2VjcmV0\x07\x1b\\");
+        assert_eq!(osc52('c', Wrap::Screen, "secret"), "\x1bP\x1b]52;c;c2VjcmV0\x07\x1b\\");
+    }
+
+    #[test]
+    fn clipboard_clear_test() {
+        let dir = std::env::temp_dir().join(format!("hel_clipboard_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let clipboard = |name: &str| {
+            let file = dir.join(name).display().to_string();
+            Clipboard::new(&format!("tee {}", file), Some(&format!("cat {}", file)), Some(&format!("rm {}", file)))
+                .unwrap()
+        };
+        for (name, value) in [("same", "secret"), ("changed", "other")] {
+            std::fs::write(dir.join(name), value).unwrap();
+            assert!(clipboard(name).clear_after("secret", 1).unwrap());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2500));
+        assert!(!dir.join("same").exists());
+        assert_eq!(std::fs::read_to_string(dir.join("changed")).unwrap(), "other");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,30 +1,55 @@
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
 use regex::Regex;
 use sha1::{Digest, Sha1};
 use std::cmp::min;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{BufRead, BufReader};
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::Path;
+use std::sync::Arc;
 
-use crate::parser::command_parser;
+use crate::autotype::{self, username};
+use crate::clipboard::Clipboard;
+use crate::lk::LK;
+use crate::output::{Entry, GenRow, LKResult};
+use crate::parser::{command_help, command_parser, COMMANDS_HELP};
 use crate::password::fix_password_recursion;
 use crate::password::{Name, Password, PasswordRef};
+use crate::query::Query;
 use crate::repl::LKEval;
-use crate::structs::{LKOut, Radix, CORRECT_FILE, DUMP_FILE};
+use crate::structs::{Command, EditField, LKErr, LKOut, Radix, Settings};
+use crate::utils::date::Date;
 use crate::utils::editor::password;
-use crate::utils::{call_cmd_with_input, get_cmd_args_from_command, get_copy_command_from_env, rnd};
+use crate::utils::{call_cmd_with_input, did_you_mean, get_cmd_args_from_command, quote_name, rnd};
 
-impl<'a> LKEval<'a> {
+impl LKEval {
     pub fn get_password(&self, name: &String) -> Option<PasswordRef> {
         match self.state.lock().borrow().ls.get(name) {
             Some(pwd) => Some(pwd.clone()),
             None => match self.state.lock().borrow().db.get(name) {
                 Some(pwd) => Some(pwd.clone()),
-                None => None,
+                None => match self.state.lock().borrow().get_alias(name) {
+                    Some(pwd) => Some(pwd),
+                    None if name.contains('/') || name == ".." => self.state.lock().borrow().resolve(name).flatten(),
+                    None => None,
+                },
             },
         }
     }
 
+    /// Point at the entry the user probably meant when a name was not found.
+    pub fn suggest_name(&self, out: &LKOut, name: &str) {
+        let lk = self.state.lock();
+        let lk = lk.borrow();
+        let aliases = lk.db.values().filter_map(|p| p.lock().borrow().alias.clone()).collect::<Vec<String>>();
+        let names = lk.db.keys().chain(aliases.iter()).map(|n| n.as_str());
+        if let Some(found) = did_you_mean(name, names) {
+            out.e(format!("did you mean {}?", quote_name(found)));
+        }
+    }
+
     pub fn read_master(&self, out: &LKOut, pwd: PasswordRef, read: bool) -> Option<String> {
         if read {
             match self.read_master(&out, pwd.clone(), false) {
@@ -88,6 +113,23 @@ impl<'a> LKEval<'a> {
         }
     }
 
+    /// The master of `pwd` derived from the cached secrets alone, without asking for one and
+    /// without caching the secrets of the folders in between.
+    pub fn cached_master(&self, pwd: &PasswordRef) -> Option<String> {
+        let parent = pwd.lock().borrow().parent.clone();
+        let name = match &parent {
+            Some(p) => p.lock().borrow().name.to_string(),
+            None => "/".to_string(),
+        };
+        if let Some(secret) = self.state.lock().borrow().secrets.get(&name) {
+            return Some(secret.to_string());
+        }
+        let parent = parent?;
+        let master = self.cached_master(&parent)?;
+        let secret = parent.lock().borrow().encode(&master);
+        Some(secret)
+    }
+
     pub fn cmd_add(&self, out: &LKOut, name: &PasswordRef) {
         let state_cell = self.state.lock();
         let mut state = state_cell.borrow_mut();
@@ -96,9 +138,12 @@ impl<'a> LKEval<'a> {
             let pwname = &name.lock().borrow().name.to_string();
             if let Some(oldname) = state.db.get(pwname) {
                 if name.lock().borrow().to_string() != oldname.lock().borrow().to_string() {
-                    out.e(format!("error: password {} already exist", pwname));
+                    out.fail(LKErr::AlreadyExists(format!("password {} already exist", pwname)));
                 }
             } else {
+                if name.lock().borrow().parent.is_none() {
+                    name.lock().borrow_mut().parent = state.cwd.clone();
+                }
                 state.db.insert(pwname.to_string(), name.clone());
                 fix = true;
             }
@@ -112,13 +157,147 @@ impl<'a> LKEval<'a> {
         let pwd = match self.state.lock().borrow().ls.get(name) {
             Some(pwd) => pwd.clone(),
             None => {
-                out.e(format!("error: {} not found", name));
+                out.fail(LKErr::NotFound(format!("{} not found", name)));
                 return;
             }
         };
         self.cmd_add(&out, &pwd);
     }
 
+    pub fn cmd_cd(&self, out: &LKOut, folder: &Option<String>) {
+        let folder = match folder {
+            None => None,
+            Some(f) => match self.state.lock().borrow().resolve(f) {
+                Some(folder) => folder,
+                None => match self.get_password(f) {
+                    Some(p) => Some(p),
+                    None => {
+                        out.fail(LKErr::NotFound(format!("folder {} not found", f)));
+                        self.suggest_name(out, f);
+                        return;
+                    }
+                },
+            },
+        };
+        self.state.lock().borrow_mut().cwd = folder;
+    }
+
+    pub fn cmd_add_default(&self, out: &LKOut, name: &Name) {
+        let mut pwd = self.default_password(name);
+        pwd.parent = self.state.lock().borrow().cwd.clone();
+        self.state.lock().borrow().inherit_defaults(&mut pwd);
+        self.cmd_add(out, &Password::from_password(pwd));
+    }
+
+    /// A new entry that only has a name, using the mode and seq from the settings.
+    fn default_password(&self, name: &Name) -> Password {
+        let settings = self.state.lock().borrow().settings.clone();
+        Password::new(None, name.to_string(), None, settings.mode, settings.seq, Date::now(), None)
+    }
+
+    pub fn cmd_set(&self, out: &LKOut, key: &str, value: &str) {
+        // only typed settings are saved, so the init file sourcing itself or a script does not rewrite it
+        let file = self.interactive.then(|| self.state.lock().borrow().config.path("init"));
+        self.set_and_save(out, file.as_deref(), key, value);
+    }
+
+    /// Change a setting and keep it in the init `file`, unless it already had that value.
+    pub fn set_and_save(&self, out: &LKOut, file: Option<&Path>, key: &str, value: &str) {
+        let state = self.state.lock();
+        let old = state.borrow().settings.get(key);
+        if let Err(e) = state.borrow_mut().settings.set(key, value) {
+            out.fail(LKErr::Error(e));
+            return;
+        }
+        let file = match file {
+            Some(file) if state.borrow().settings.get(key) != old => file,
+            _ => return,
+        };
+        if let Err(e) = save_setting(file, key, value) {
+            out.fail(LKErr::Io(format!("failed to save setting to {}: {}", file.display(), e)));
+        }
+    }
+
+    pub fn cmd_get(&self, out: &LKOut, key: &Option<String>) {
+        let settings = self.state.lock().borrow().settings.clone();
+        match key {
+            Some(key) => match settings.get(key) {
+                Some(value) => out.o(value),
+                None => {
+                    out.fail(LKErr::Error(format!("unknown setting {}, use one of {}", key, Settings::KEYS.join(", "))))
+                }
+            },
+            None => {
+                for key in Settings::KEYS {
+                    out.o(format!("{:<6} {}", key, settings.get(key).unwrap_or_default()));
+                }
+            }
+        }
+    }
+
+    pub fn cmd_config(&self, out: &LKOut) {
+        let config = self.state.lock().borrow().config.clone();
+        out.o(format!("{:<8} {}", "file", config.file.display()));
+        if let Some((profile, source)) = &config.profile {
+            out.o(format!("{:<8} {:<24} {}", "profile", profile, source));
+        }
+        for (key, value, source) in config.values.iter() {
+            let value = match value {
+                Some(v) => format!("{:?}", v),
+                None => "(auto)".to_string(),
+            };
+            out.o(format!("{:<8} {:<24} {}", key, value, source));
+        }
+        for err in config.errors.iter() {
+            out.e(format!("error: {}", err));
+        }
+    }
+
+    pub fn cmd_defaults(&self, out: &LKOut, folder: &String, fields: &Vec<EditField>) {
+        let name = if folder == "/" {
+            folder.to_string()
+        } else {
+            match self.get_password(folder) {
+                Some(p) => p.lock().borrow().name.to_string(),
+                None => {
+                    out.fail(LKErr::NotFound(format!("folder {} not found", folder)));
+                    self.suggest_name(out, folder);
+                    return;
+                }
+            }
+        };
+        let state = self.state.lock();
+        let mut state = state.borrow_mut();
+        if fields.is_empty() {
+            match state.defaults.get(&name) {
+                Some(d) => out.o(d.iter().map(|f| f.to_string()).collect::<Vec<String>>().join(" ")),
+                None => out.o(format!("no defaults for {}", quote_name(&name))),
+            }
+            return;
+        }
+        let defaults = state.defaults.entry(name).or_default();
+        for field in fields {
+            defaults.retain(|f| std::mem::discriminant(f) != std::mem::discriminant(field));
+            defaults.push(field.clone());
+        }
+    }
+
+    pub fn cmd_undefaults(&self, out: &LKOut, folder: &String) {
+        // the defaults of a removed entry are only found by its name
+        let name = match self.get_password(folder) {
+            Some(p) if folder != "/" => p.lock().borrow().name.to_string(),
+            _ => folder.to_string(),
+        };
+        match self.state.lock().borrow_mut().defaults.remove(&name) {
+            Some(_) => out.o(format!("Removed defaults for {}", name)),
+            None => out.fail(LKErr::NotFound(format!("defaults for {} not found", name))),
+        }
+    }
+
+    pub fn cmd_pwd(&self, out: &LKOut) {
+        out.o(LK::path(&self.state.lock().borrow().cwd));
+    }
+
     pub fn cmd_mv(&self, out: &LKOut, name: &String, folder: &String) {
         match self.get_password(name) {
             Some(pwd) => {
@@ -130,11 +309,17 @@ impl<'a> LKEval<'a> {
                             pwd.lock().borrow_mut().parent = Some(fld.clone());
                             fix_password_recursion(pwd.clone());
                         }
-                        None => out.e(format!("error: folder {} not found", folder)),
+                        None => {
+                            out.fail(LKErr::NotFound(format!("folder {} not found", folder)));
+                            self.suggest_name(out, folder);
+                        }
                     }
                 }
             }
-            None => out.e(format!("error: password with name {} not found", name)),
+            None => {
+                out.fail(LKErr::NotFound(format!("password with name {} not found", name)));
+                self.suggest_name(out, name);
+            }
         }
     }
 
@@ -157,7 +342,8 @@ impl<'a> LKEval<'a> {
                     self.cmd_correct(&out, &"/".to_string(), true, Some(pwd.clone()));
                     self.state.lock().borrow_mut().secrets.insert("/".to_string(), pwd);
                 } else {
-                    out.e(format!("error: password with name {} not found", name));
+                    out.fail(LKErr::NotFound(format!("password with name {} not found", name)));
+                    self.suggest_name(out, name);
                 }
             }
         }
@@ -171,10 +357,173 @@ impl<'a> LKEval<'a> {
                     None => None,
                 }
             }
-            None => out.e("error: password not found".to_string()),
+            None => out.fail(LKErr::NotFound("password not found".to_string())),
         }
     }
 
+    /// Show `preview` and ask on the terminal, stdout and the command stream are left alone. Without
+    /// a terminal to ask on, the answer is no.
+    pub fn confirm(&self, out: &LKOut, preview: &[String], prompt: &str) -> bool {
+        if !self.interactive || !std::io::stdin().is_terminal() {
+            out.e("warning: no terminal to confirm on, use -y to apply without asking".to_string());
+            return false;
+        }
+        for line in preview {
+            eprintln!("{}", line);
+        }
+        eprint!("{}", prompt);
+        std::io::stderr().flush().ok();
+        let mut answer = String::new();
+        match std::io::stdin().read_line(&mut answer) {
+            Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+            Err(_) => false,
+        }
+    }
+
+    pub fn cmd_edit(&self, out: &LKOut, name: &String, fields: &Vec<EditField>, yes: bool) {
+        let pwd = match self.get_password(name) {
+            Some(p) => p,
+            None => {
+                out.fail(LKErr::NotFound(format!("password with name {} not found", name)));
+                self.suggest_name(out, name);
+                return;
+            }
+        };
+        let new = Password::from_password_ref(&pwd.lock().borrow());
+        for field in fields {
+            new.lock().borrow_mut().edit(field);
+        }
+        let name = pwd.lock().borrow().name.to_string();
+        let mut preview =
+            vec![format!("- {}", pwd.lock().borrow().to_string()), format!("+ {}", new.lock().borrow().to_string())];
+        let pass = match self.read_master(out, pwd.clone(), true) {
+            Some(master) => {
+                let (old, pass) = (pwd.lock().borrow().encode(&master), new.lock().borrow().encode(&master));
+                if old == pass {
+                    preview.push(format!("password stays {}", pass));
+                } else {
+                    preview.push(format!("password changes from {} to {}", old, pass));
+                }
+                Some(pass)
+            }
+            None => {
+                out.e(format!("warning: master for {} not found, can not show the new password", name));
+                None
+            }
+        };
+        if yes {
+            for line in preview {
+                out.o(line);
+            }
+        } else if !self.confirm(out, &preview, "Apply changes? [y/N] ") {
+            out.fail(LKErr::Error(format!("{} not changed", name)));
+            return;
+        }
+        for field in fields {
+            pwd.lock().borrow_mut().edit(field);
+        }
+        if let Some(pass) = pass {
+            self.cmd_correct(out, &name, true, Some(pass));
+        }
+        out.o(format!("changed {}", name));
+    }
+
+    pub fn cmd_rename(&self, out: &LKOut, name: &String, alias: &Option<String>) {
+        let pwd = match self.get_password(name) {
+            Some(p) => p,
+            None => {
+                out.fail(LKErr::NotFound(format!("password with name {} not found", name)));
+                self.suggest_name(out, name);
+                return;
+            }
+        };
+        if let Some(alias) = alias {
+            let other = match self.state.lock().borrow().db.get(alias) {
+                Some(p) => Some(p.clone()),
+                None => self.state.lock().borrow().get_alias(alias),
+            };
+            if other.is_some() && !Arc::ptr_eq(other.as_ref().unwrap(), &pwd) {
+                out.fail(LKErr::AlreadyExists(format!("name {} is already used", alias)));
+                return;
+            }
+        }
+        pwd.lock().borrow_mut().alias = alias.clone();
+    }
+
+    pub fn cmd_show(&self, out: &LKOut, name: &String) {
+        let pwd = match self.get_password(name) {
+            Some(p) => p,
+            None => {
+                out.fail(LKErr::NotFound(format!("name {} not found", name)));
+                self.suggest_name(out, name);
+                return;
+            }
+        };
+        let mut folders = vec![];
+        let mut parent = pwd.lock().borrow().parent.clone();
+        while let Some(p) = parent {
+            folders.push(p.lock().borrow().name.to_string());
+            parent = p.lock().borrow().parent.clone();
+        }
+        folders.push("/".to_string());
+        let (name, secrets) = {
+            let state = self.state.lock();
+            let state = state.borrow();
+            let name = pwd.lock().borrow().name.to_string();
+            let secrets = [name.clone()]
+                .iter()
+                .chain(folders.iter())
+                .filter(|f| state.secrets.contains_key(*f))
+                .map(|f| quote_name(f))
+                .collect::<Vec<String>>();
+            (name, secrets)
+        };
+        let keys = {
+            let state = self.state.lock();
+            let mut keys = state
+                .borrow()
+                .ls
+                .iter()
+                .filter(|(_, p)| Arc::ptr_eq(p, &pwd))
+                .map(|(k, _)| k.to_string())
+                .collect::<Vec<String>>();
+            keys.sort();
+            keys
+        };
+        // only derive the password from cached secrets, show never asks for a master password
+        let correct = if secrets.is_empty() {
+            "unknown, no cached secret".to_string()
+        } else {
+            let cached = self.state.lock().borrow().secrets.get(&name).cloned();
+            match cached.or_else(|| self.cached_master(&pwd).map(|m| pwd.lock().borrow().encode(&m))) {
+                Some(pass) => match load_correct(&self.state.lock().borrow().config.path("correct")) {
+                    Ok(lines) if lines.contains(&correct_hash(&name, &pass)) => "yes".to_string(),
+                    _ => "no".to_string(),
+                },
+                None => "unknown, master not found".to_string(),
+            }
+        };
+        let pwd = pwd.lock();
+        let pwd = pwd.borrow();
+        let none = || "-".to_string();
+        let field = |label: &str, value: String| out.o(format!("{:<9} {}", label, value));
+        field("name:", quote_name(&pwd.name));
+        field("alias:", pwd.alias.as_ref().map(|a| quote_name(a)).unwrap_or_else(none));
+        field("prefix:", pwd.prefix.as_ref().map(|p| quote_name(p)).unwrap_or_else(none));
+        field("length:", pwd.length.map(|l| l.to_string()).unwrap_or_else(|| "full".to_string()));
+        field("mode:", pwd.mode.to_string());
+        field("seq:", pwd.seq.to_string());
+        field("date:", format!("{}, {} days old", pwd.date, pwd.date.days_until(&Date::now())));
+        field("comment:", pwd.comment.clone().unwrap_or_else(none));
+        field("folders:", folders.iter().map(|f| quote_name(f)).collect::<Vec<String>>().join(" > "));
+        field("secrets:", if secrets.is_empty() { none() } else { secrets.join(", ") });
+        field("correct:", correct);
+        field("ls key:", if keys.is_empty() { none() } else { keys.join(", ") });
+        let defaults = self.state.lock().borrow().defaults.get(&pwd.name).cloned().unwrap_or_default();
+        let defaults = defaults.iter().map(|f| f.to_string()).collect::<Vec<String>>().join(" ");
+        field("defaults:", if defaults.is_empty() { none() } else { defaults });
+    }
+
     pub fn cmd_enc(&self, out: &LKOut, name: &String) -> Option<(String, String)> {
         let root_folder = "/".to_string();
         let (name, pass) = if name == "/" && self.state.lock().borrow().secrets.contains_key(&root_folder) {
@@ -183,7 +532,8 @@ impl<'a> LKEval<'a> {
             let pwd = match self.get_password(name) {
                 Some(p) => p.clone(),
                 None => {
-                    out.e(format!("error: name {} not found", name));
+                    out.fail(LKErr::NotFound(format!("name {} not found", name)));
+                    self.suggest_name(out, name);
                     return None;
                 }
             };
@@ -194,7 +544,7 @@ impl<'a> LKEval<'a> {
                 match self.read_master(&out, pwd.clone(), true) {
                     Some(sec) => (name.clone(), pwd.lock().borrow().encode(sec.as_str())),
                     None => {
-                        out.e(format!("error: master for {} not found", name));
+                        out.fail(LKErr::MasterMissing(format!("master for {} not found", name)));
                         return None;
                     }
                 }
@@ -202,11 +552,71 @@ impl<'a> LKEval<'a> {
         };
         if out.active() {
             out.o(pass.clone());
+            out.set_result(LKResult::Password {
+                name: name.clone(),
+                password: pass.clone(),
+            });
             self.cmd_correct(&out, &name, true, Some(pass.clone()));
         }
         Some((name, pass))
     }
 
+    pub fn cmd_type(&self, out: &LKOut, name: &String, user: bool) {
+        let pwd = match self.get_password(name) {
+            Some(p) => p,
+            None => {
+                out.fail(LKErr::NotFound(format!("name {} not found", name)));
+                self.suggest_name(out, name);
+                return;
+            }
+        };
+        let comment = pwd.lock().borrow().comment.clone().unwrap_or_default();
+        let user = match (user, username(&comment)) {
+            (false, _) => None,
+            (true, Some(u)) => Some(u),
+            (true, None) => {
+                out.fail(LKErr::NotFound(format!("no user=NAME in the comment of {}", name)));
+                return;
+            }
+        };
+        let (tool, args) = match autotype::detect() {
+            Some(t) => t,
+            None => {
+                out.fail(LKErr::Error("no wtype, xdotool or ydotool found to type with".to_string()));
+                return;
+            }
+        };
+        // the password stays out of `out`, the warnings of enc do not
+        let enc = LKOut::new();
+        let encoded = self.cmd_enc(&enc, name);
+        enc.copy_err(out);
+        let (name, pass) = match encoded {
+            Some(p) => p,
+            None => {
+                if enc.error().is_none() {
+                    out.fail(LKErr::Error(format!("failed to encode {}", name)));
+                }
+                return;
+            }
+        };
+        let text = match &user {
+            Some(u) => format!("{}\t{}", u, pass),
+            None => pass,
+        };
+        std::thread::sleep(std::time::Duration::from_secs(self.state.lock().borrow().settings.delay as u64));
+        match call_cmd_with_input(&tool, &args, &text) {
+            Ok(_) => {
+                let what = if user.is_some() {
+                    "user and password"
+                } else {
+                    "password"
+                };
+                out.o(format!("Typed {} for {} with {}", what, name, tool))
+            }
+            Err(e) => out.fail(LKErr::Io(format!("failed to type with {}: {}", tool, e))),
+        }
+    }
+
     pub fn cmd_pb(&self, out: &LKOut, command: &String) {
         match command_parser::cmd(command) {
             Ok(cmd) => {
@@ -214,8 +624,17 @@ impl<'a> LKEval<'a> {
                 let data = print.out.data();
                 print.out.copy_err(&out);
                 if data.len() > 0 {
-                    let (copy_command, copy_cmd_args) = get_copy_command_from_env();
-                    match call_cmd_with_input(&copy_command, &copy_cmd_args, &data) {
+                    let settings = self.state.lock().borrow().settings.clone();
+                    let config = self.state.lock().borrow().config.clone();
+                    let clipboard = match Clipboard::detect(&config, settings.clip == "primary") {
+                        Ok(c) => c,
+                        Err(e) => {
+                            out.fail(e);
+                            return;
+                        }
+                    };
+                    let copy_command = &clipboard.name;
+                    match clipboard.copy(&data) {
                         Ok(s) if s.len() > 0 => {
                             out.o(format!(
                                 "Copied output with the command {}, and got following output:",
@@ -224,40 +643,111 @@ impl<'a> LKEval<'a> {
                             out.o(s.trim().to_string());
                         }
                         Ok(_) => out.o(format!("Copied output with command {}", copy_command)),
-                        Err(e) => out.e(format!("error: failed to copy: {}", e.to_string())),
+                        Err(e) => {
+                            out.fail(LKErr::Io(format!("failed to copy: {}", e)));
+                            return;
+                        }
                     };
+                    match clipboard.clear_after(&data, settings.clear) {
+                        Ok(true) => out.o(format!("Clearing it in {} seconds", settings.clear)),
+                        Ok(false) => (),
+                        Err(e) => out.e(format!("warning: failed to schedule clearing the clipboard: {}", e)),
+                    }
                 }
             }
-            Err(e) => out.e(format!("error: faild to parse command {}: {}", command, e.to_string())),
+            Err(e) => out.fail(LKErr::Error(format!("faild to parse command {}: {}", command, e))),
         };
     }
 
+    pub fn cmd_pipe(&self, out: &LKOut, data: &str, command: &str) {
+        if let Some(output) = run_shell(out, command, data) {
+            for line in output.lines() {
+                out.o(line.to_string());
+            }
+        }
+    }
+
+    pub fn cmd_redirect(&self, out: &LKOut, data: &str, file: &str, append: bool) {
+        let path = match shellexpand::full(file) {
+            Ok(p) => p.into_owned(),
+            Err(e) => {
+                out.fail(LKErr::Io(format!("failed to expand file name {}: {}", file, e)));
+                return;
+            }
+        };
+        let mut options = fs::OpenOptions::new();
+        options.create(true).append(append).write(true).truncate(!append);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let written = options.open(&path).and_then(|mut f| {
+            if data.is_empty() {
+                Ok(())
+            } else {
+                writeln!(f, "{}", data)
+            }
+        });
+        if let Err(e) = written {
+            out.fail(LKErr::Io(format!("failed to write to file {}: {}", file, e)));
+        }
+    }
+
+    pub fn cmd_help(&self, out: &LKOut, topic: &Option<String>) {
+        let topic = match topic {
+            Some(t) => t,
+            None => {
+                for help in COMMANDS_HELP {
+                    out.o(format!("{:<10} {}", help.name, help.summary));
+                }
+                out.o("Type \"help COMMAND\" to see the usage of a command.".to_string());
+                out.o("Separate commands with ;, pipe the output to a shell command with | or write it to a file with > and >>.".to_string());
+                out.o("From the shell, \"hel [--json] COMMAND ARGS...\" runs one command and exits, \"hel --batch [--strict]\" runs one command per line from stdin without touching the history, --strict stops at the first error, --json prints the results as JSON.".to_string());
+                return;
+            }
+        };
+        let help = match command_help(topic) {
+            Some(h) => h,
+            None => {
+                out.fail(LKErr::Error(format!("unknown command {}, type help for the list of commands", topic)));
+                return;
+            }
+        };
+        out.o(format!("{} - {}", help.name, help.summary));
+        out.o("Usage:".to_string());
+        for syntax in help.syntax {
+            out.o(format!("  {}", syntax));
+        }
+        if !help.args.is_empty() {
+            out.o("Arguments:".to_string());
+            let width = help.args.iter().map(|(a, _)| a.len()).max().unwrap_or(0);
+            for (arg, desc) in help.args {
+                out.o(format!("  {:<width$}  {}", arg, desc, width = width));
+            }
+        }
+        out.o("Examples:".to_string());
+        for example in help.examples {
+            out.o(format!("  {}", example));
+        }
+    }
+
     pub fn cmd_source(&self, out: &LKOut, source: &String) -> bool {
         let script = if source.trim().ends_with("|") {
-            let (cmd, args) = match get_cmd_args_from_command(source.trim().trim_end_matches('|')) {
-                Ok(c) => c,
-                Err(e) => {
-                    out.e(format!("error: failed to parse command {:?}: {}", source, e.to_string()));
-                    return false;
-                }
-            };
-            match call_cmd_with_input(&cmd, &args, "") {
-                Ok(o) => o,
-                Err(e) => {
-                    out.e(format!("error: failed to execute command {}: {}", cmd, e.to_string()));
-                    return false;
-                }
+            match run_shell(out, source.trim().trim_end_matches('|'), "") {
+                Some(o) => o,
+                None => return false,
             }
         } else {
             let script = shellexpand::full(source).unwrap().into_owned();
             match std::fs::read_to_string(script) {
                 Ok(script) => script,
                 Err(e) => {
-                    out.e(format!("error: failed to read file {}: {}", source, e.to_string()));
+                    out.fail(LKErr::Io(format!("failed to read file {}: {}", source, e)));
                     return false;
                 }
             }
         };
+        // scripts like dumps name their folders with ^folder, so run them from the top
+        let cwd = self.state.lock().borrow_mut().cwd.take();
+        defer! { self.state.lock().borrow_mut().cwd = cwd.clone(); }
         match command_parser::script(&script) {
             Ok(cmd_list) => {
                 for cmd in cmd_list {
@@ -268,69 +758,47 @@ impl<'a> LKEval<'a> {
                     }
                 }
             }
-            Err(e) => {
-                out.e(format!("error: {}", e.to_string()));
-            }
+            Err(e) => out.fail(LKErr::ParseError(script.to_string(), e)),
         };
         false
     }
 
     pub fn cmd_dump(&self, out: &LKOut, script: &Option<String>) {
         let script = match script {
-            Some(p) => p,
-            None => DUMP_FILE.to_str().unwrap(),
+            Some(p) => p.to_string(),
+            None => self.state.lock().borrow().config.path("dump").display().to_string(),
         };
-        let script = shellexpand::full(script).unwrap().into_owned();
-        fn save_dump(data: &HashMap<Name, PasswordRef>, script: &String) -> std::io::Result<()> {
+        let script = shellexpand::full(&script).unwrap().into_owned();
+        fn save_dump(lines: &[String], script: &String) -> std::io::Result<()> {
             let file = fs::File::create(script)?;
             let mut writer = BufWriter::new(file);
-            let mut vals = data.values().map(|v| v.clone()).collect::<Vec<PasswordRef>>();
-            vals.sort_by(|a, b| a.lock().borrow().name.cmp(&b.lock().borrow().name));
-            for pwd in vals {
-                writeln!(writer, "add {}", pwd.lock().borrow().to_string())?
+            for line in lines {
+                writeln!(writer, "{}", line)?
             }
             Ok(())
         }
+        let lines = dump_script(&self.state.lock().borrow());
         if script.trim().starts_with("|") {
-            let (cmd, args) = match get_cmd_args_from_command(script.trim().trim_start_matches('|')) {
-                Ok(c) => c,
-                Err(e) => {
-                    out.e(format!("error: failed to parse command {:?}: {}", script, e.to_string()));
-                    return;
-                }
-            };
-            let data = self
-                .state
-                .lock()
-                .borrow()
-                .db
-                .values()
-                .map(|v| format!("add {}", v.lock().borrow().to_string()))
-                .collect::<Vec<String>>()
-                .join("\n");
-            let output = match call_cmd_with_input(&cmd, &args, data.as_str()) {
-                Ok(o) => o,
-                Err(e) => {
-                    out.e(format!("error: failed to execute command {}: {}", cmd, e.to_string()));
-                    return;
-                }
+            let command = script.trim().trim_start_matches('|');
+            let data = lines.join("\n");
+            let output = match run_shell(out, command, data.as_str()) {
+                Some(o) => o,
+                None => return,
             };
             if output.len() > 0 {
-                out.e(format!("Passwords saved to command {} and got following output:", cmd));
+                out.e(format!("Passwords saved to command {} and got following output:", command.trim()));
                 out.o(output);
             } else {
-                out.o(format!("Passwords saved to command {}", cmd));
+                out.o(format!("Passwords saved to command {}", command.trim()));
             }
         } else if script.trim() == "-" {
-            let mut vals = (&self.state.lock().borrow().db).values().map(|v| v.clone()).collect::<Vec<PasswordRef>>();
-            vals.sort_by(|a, b| a.lock().borrow().name.cmp(&b.lock().borrow().name));
-            for pwd in vals {
-                out.o(format!("add {}", pwd.lock().borrow().to_string()))
+            for line in lines {
+                out.o(line)
             }
         } else {
-            match save_dump(&self.state.lock().borrow().db, &script) {
+            match save_dump(&lines, &script) {
                 Ok(()) => out.o(format!("Passwords saved to file {}", script)),
-                Err(e) => out.e(format!("error: failed to dump passswords to {}: {}", script, e.to_string())),
+                Err(e) => out.fail(LKErr::Io(format!("failed to dump passswords to {}: {}", script, e))),
             };
         }
     }
@@ -339,35 +807,223 @@ impl<'a> LKEval<'a> {
     where
         F: Fn(&PasswordRef, &PasswordRef) -> std::cmp::Ordering,
     {
-        let re = match Regex::new(&filter) {
-            Ok(re) => re,
+        let query = match Query::parse(&filter) {
+            Ok(q) => q,
             Err(e) => {
-                out.e(format!("error: failed to parse re: {:?}", e));
+                out.fail(LKErr::Error(e));
                 return;
             }
         };
+        let mut tmp = self.filter_entries(&query);
+        tmp.sort_by(sort_by);
+        self.print_ls(out, tmp);
+    }
+
+    /// Entries in the current folder matching the query, sorted by name.
+    fn filter_entries(&self, query: &Query) -> Vec<PasswordRef> {
         let mut tmp: Vec<PasswordRef> = vec![];
+        let cwd = self.state.lock().borrow().cwd.clone();
         for (_, name) in &self.state.lock().borrow().db {
-            if re.find(&name.lock().borrow().to_string()).is_some() {
-                tmp.push(name.clone());
-            } else if re.find(&name.lock().borrow().name).is_some() {
-                tmp.push(name.clone());
-            } else if name.lock().borrow().comment.is_some()
-                && re.find(&name.lock().borrow().comment.as_ref().unwrap()).is_some()
-            {
+            let in_cwd = match (&cwd, &name.lock().borrow().parent) {
+                (Some(c), Some(p)) => Arc::ptr_eq(c, p),
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+            if in_cwd && query.matches(&name.lock().borrow()) {
                 tmp.push(name.clone());
             }
         }
         tmp.sort_by(|a, b| a.lock().borrow().name.cmp(&b.lock().borrow().name));
-        tmp.sort_by(sort_by);
+        tmp
+    }
+
+    pub fn cmd_foreach(&self, out: &LKOut, filter: &str, template: &str, yes: &Option<bool>) -> bool {
+        let query = match Query::parse(filter) {
+            Ok(q) => q,
+            Err(e) => {
+                out.fail(LKErr::Error(e));
+                return false;
+            }
+        };
+        let entries = self.filter_entries(&query);
+        if entries.is_empty() {
+            out.fail(LKErr::Error(format!("no entries match {}", filter)));
+            return false;
+        }
+        let mut lines = vec![];
+        for pwd in &entries {
+            let line = {
+                let p = pwd.lock();
+                let p = p.borrow();
+                template
+                    .replace("{name}", &quote_name(&p.name))
+                    .replace("{mode}", &p.mode.to_string())
+                    .replace("{seq}", &p.seq.to_string())
+                    .replace("{date}", &p.date.to_string())
+            };
+            if let Err(e) = command_parser::cmd(&line) {
+                out.fail(LKErr::ParseError(line, e));
+                return false;
+            }
+            lines.push(line);
+        }
+        let preview = lines.iter().map(|line| format!("  {}", line)).collect::<Vec<String>>();
+        match yes {
+            Some(_) => preview.into_iter().for_each(|line| out.o(line)),
+            None => {
+                if !self.confirm(out, &preview, &format!("Run {} commands? [y/N] ", lines.len())) {
+                    out.fail(LKErr::Error("nothing done".to_string()));
+                    return false;
+                }
+            }
+        }
+        if *yes == Some(false) {
+            return false;
+        }
+        for line in &lines {
+            // the foreach confirmation covers the edits, so don't ask for every entry again
+            let cmd = match command_parser::cmd(line).unwrap() {
+                Command::Edit(name, fields, _) => Command::Edit(name, fields, true),
+                cmd => cmd,
+            };
+            // eval_cmd instead of eval, only the foreach line goes to the history
+            let each = LKOut::new();
+            let (quit, _) = self.eval_cmd(&each, &cmd);
+            each.copy(out);
+            if quit {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn cmd_find(&self, out: &LKOut, query: &str) {
+        let matcher = SkimMatcherV2::default().smart_case();
+        let mut found: Vec<(i64, PasswordRef)> = vec![];
+        for pwd in self.state.lock().borrow().db.values() {
+            let fields = {
+                let p = pwd.lock();
+                let p = p.borrow();
+                let mut fields = vec![(p.name.to_string(), 2)];
+                for text in [&p.alias, &p.comment, &p.prefix].into_iter().flatten() {
+                    fields.push((text.to_string(), 1));
+                }
+                fields.push((p.to_string(), 1));
+                fields
+            };
+            let score = query.split_whitespace().try_fold(0, |score, term| {
+                fields
+                    .iter()
+                    .filter_map(|(text, weight)| matcher.fuzzy_match(text, term).map(|s| s * weight))
+                    .max()
+                    .map(|s| score + s)
+            });
+            if let Some(score) = score {
+                found.push((score, pwd.clone()));
+            }
+        }
+        found.sort_by(|(a, x), (b, y)| b.cmp(a).then_with(|| x.lock().borrow().name.cmp(&y.lock().borrow().name)));
+        self.print_ls(out, found.into_iter().map(|(_, p)| p).collect());
+    }
+
+    pub fn cmd_tree(&self, out: &LKOut, folder: &Option<String>) {
+        let top = match folder {
+            Some(f) => match self.get_password(f) {
+                Some(p) => Some(p),
+                None => {
+                    out.fail(LKErr::NotFound(format!("folder {} not found", f)));
+                    self.suggest_name(out, f);
+                    return;
+                }
+            },
+            None => None,
+        };
+        let mut children: HashMap<String, Vec<PasswordRef>> = HashMap::new();
+        for pwd in self.state.lock().borrow().db.values() {
+            let parent = match &pwd.lock().borrow().parent {
+                Some(p) => p.lock().borrow().name.to_string(),
+                None => "/".to_string(),
+            };
+            children.entry(parent).or_default().push(pwd.clone());
+        }
+        for list in children.values_mut() {
+            list.sort_by(|a, b| a.lock().borrow().name.cmp(&b.lock().borrow().name));
+        }
+        fn count(children: &HashMap<String, Vec<PasswordRef>>, name: &str) -> usize {
+            children
+                .get(name)
+                .map_or(0, |l| l.iter().map(|c| 1 + count(children, &c.lock().borrow().name)).sum())
+        }
+        fn walk(
+            children: &HashMap<String, Vec<PasswordRef>>,
+            name: &str,
+            indent: &str,
+            lines: &mut Vec<(String, PasswordRef)>,
+        ) {
+            let list = match children.get(name) {
+                Some(l) => l,
+                None => return,
+            };
+            for (i, pwd) in list.iter().enumerate() {
+                let last = i + 1 == list.len();
+                lines.push((format!("{}{}", indent, if last { "└── " } else { "├── " }), pwd.clone()));
+                let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+                walk(children, &pwd.lock().borrow().name, &indent, lines);
+            }
+        }
+        let mut lines = vec![];
+        let top_name = match &top {
+            Some(p) => {
+                lines.push(("".to_string(), p.clone()));
+                p.lock().borrow().name.to_string()
+            }
+            None => "/".to_string(),
+        };
+        walk(&children, &top_name, "", &mut lines);
+        let label = |name: &str| {
+            let mut label = String::new();
+            let below = count(&children, name);
+            if below > 0 {
+                label.push_str(&format!(" ({})", below));
+            }
+            if self.state.lock().borrow().secrets.contains_key(name) {
+                label.push_str(" unlocked");
+            }
+            label
+        };
+        if top.is_none() {
+            out.o(format!("/{}", label("/")));
+        }
         self.state.lock().borrow_mut().ls.clear();
-        let mut counter = 1;
-        for pwd in tmp {
+        for (counter, (indent, pwd)) in (1..).zip(lines) {
+            let key = Radix::new(counter, 36).unwrap().to_string();
+            let name = pwd.lock().borrow().name.to_string();
+            out.o(format!("{}{} {}{}", indent, key, quote_name(&name), label(&name)));
+            self.state.lock().borrow_mut().ls.insert(key, pwd);
+        }
+    }
+
+    /// Print entries like ls does and remember their short keys.
+    fn print_ls(&self, out: &LKOut, list: Vec<PasswordRef>) {
+        self.state.lock().borrow_mut().ls.clear();
+        let mut entries = vec![];
+        for (counter, pwd) in (1..).zip(list) {
             let key = Radix::new(counter, 36).unwrap().to_string();
-            counter += 1;
             self.state.lock().borrow_mut().ls.insert(key.clone(), pwd.clone());
-            out.o(format!("{:>3} {}", key, pwd.lock().borrow().to_string()));
+            let alias = match &pwd.lock().borrow().alias {
+                Some(a) => format!(" [{}]", a),
+                None => "".to_string(),
+            };
+            out.o(format!("{:>3} {}{}", key, self.ls_line(&pwd), alias));
+            entries.push(Entry::new(&key, &pwd.lock().borrow()));
         }
+        out.set_result(LKResult::Entries { entries });
+    }
+
+    fn ls_line(&self, pwd: &PasswordRef) -> String {
+        let date = self.state.lock().borrow().settings.show_date(&pwd.lock().borrow().date);
+        let line = pwd.lock().borrow().to_string_with_date(&date);
+        line
     }
 
     pub fn cmd_correct(&self, out: &LKOut, name: &String, correct: bool, check: Option<String>) {
@@ -391,23 +1047,9 @@ impl<'a> LKEval<'a> {
             Some(v) => v,
             None => return,
         };
-        fn load_lines() -> std::io::Result<HashSet<String>> {
-            let file = fs::File::open(CORRECT_FILE.to_str().unwrap())?;
-            let reader = BufReader::new(file);
-            let mut lines = HashSet::new();
-            for line in reader.lines() {
-                lines.insert(line?.trim().to_owned());
-            }
-            Ok(lines)
-        }
-        let mut data = match load_lines() {
-            Ok(d) => d,
-            Err(_) => HashSet::new(),
-        };
-        let mut sha1 = Sha1::new();
-        sha1.update(name.to_string());
-        sha1.update(&pwd);
-        let encpwd = format!("{:x}", sha1.finalize());
+        let file = self.state.lock().borrow().config.path("correct");
+        let mut data = load_correct(&file).unwrap_or_default();
+        let encpwd = correct_hash(&name, &pwd);
         if check {
             if data.contains(&encpwd) {
                 return;
@@ -426,55 +1068,73 @@ impl<'a> LKEval<'a> {
             }
             data.remove(&encpwd);
         }
-        fn save_lines(data: &HashSet<String>) -> std::io::Result<()> {
-            let file = fs::File::create(CORRECT_FILE.to_str().unwrap())?;
+        fn save_lines(data: &HashSet<String>, file: &Path) -> std::io::Result<()> {
+            let file = fs::File::create(file)?;
             let mut writer = BufWriter::new(file);
             for entry in data {
                 writeln!(writer, "{}", entry)?;
             }
             Ok(())
         }
-        match save_lines(&data) {
+        match save_lines(&data, &file) {
             Ok(()) => out.o(format!(
                 "Hash of the password {} {} {}",
                 name,
                 if correct { "remembered to" } else { "removed from" },
-                CORRECT_FILE.to_str().unwrap()
+                file.display()
             )),
-            Err(e) => out.e(format!("error: failed to write: {}", e.to_string())),
+            Err(e) => out.fail(LKErr::Io(format!("failed to write: {}", e))),
         };
     }
 
-    pub fn cmd_gen(&self, out: &LKOut, num: &u32, name: &PasswordRef) {
+    pub fn cmd_gen_default(&self, out: &LKOut, num: &Option<u32>, name: &Name) {
+        let pwd = self.default_password(name);
+        self.cmd_gen(out, num, &Password::from_password(pwd));
+    }
+
+    pub fn cmd_gen(&self, out: &LKOut, num: &Option<u32>, name: &PasswordRef) {
         lazy_static! {
             static ref RE: Regex = Regex::new(r"^.+?(G+|X+)$").unwrap();
         }
-        let num: usize = (*num).try_into().unwrap();
+        let num = num.unwrap_or(self.state.lock().borrow().settings.gen);
+        let num: usize = num.try_into().unwrap();
         let pwd = name.lock();
         let mut genpwds: Vec<PasswordRef> = Vec::new();
-        match RE.captures(pwd.borrow().name.as_ref()) {
+        let names = match RE.captures(pwd.borrow().name.as_ref()) {
             Some(caps) => {
                 let gen = &caps[1];
                 if gen.starts_with("G") {
                     let name = pwd.borrow().name.trim_end_matches('G').to_string();
-                    for num in 1..10_u32.pow(gen.len().try_into().unwrap()) {
-                        let npwd = Password::from_password_ref(&pwd.borrow());
-                        npwd.lock().borrow_mut().name = format!("{}{}", name, num).to_string();
-                        genpwds.push(npwd);
-                    }
+                    (1..10_u32.pow(gen.len().try_into().unwrap())).map(|num| format!("{}{}", name, num)).collect()
                 } else {
                     let name = pwd.borrow().name.trim_end_matches('X').to_string();
                     let num = rnd::range(1, 10_u32.pow(gen.len().try_into().unwrap()));
-                    let npwd = Password::from_password_ref(&pwd.borrow());
-                    npwd.lock().borrow_mut().name = format!("{}{}", name, num).to_string();
-                    genpwds.push(npwd);
+                    vec![format!("{}{}", name, num)]
                 }
             }
-            None => {
-                let npwd = Password::from_password_ref(&pwd.borrow());
-                genpwds.push(npwd);
+            None => vec![pwd.borrow().name.to_string()],
+        };
+        let mut expanded = vec![];
+        // a trailing G+ alone may give more names, but placeholders must not multiply them past the cap
+        let max = MAX_GEN_NAMES.max(names.len());
+        for name in names {
+            match expand_placeholders(&name) {
+                Ok(names) => expanded.extend(names),
+                Err(e) => {
+                    out.fail(LKErr::Error(e));
+                    return;
+                }
+            }
+            if expanded.len() > max {
+                out.fail(LKErr::Error(format!("the name expands to more than {} names", MAX_GEN_NAMES)));
+                return;
             }
         }
+        for name in expanded {
+            let npwd = Password::from_password_ref(&pwd.borrow());
+            npwd.lock().borrow_mut().name = name;
+            genpwds.push(npwd);
+        }
         self.state.lock().borrow_mut().ls.clear();
         let mut counter = 1;
         let mut lspwds: Vec<(PasswordRef, String)> = Vec::new();
@@ -500,7 +1160,7 @@ impl<'a> LKEval<'a> {
                     pass
                 }
                 None => {
-                    out.e(format!("error: failed to encrypt password"));
+                    out.fail(LKErr::Error("failed to encrypt password".to_string()));
                     return;
                 }
             };
@@ -511,12 +1171,155 @@ impl<'a> LKEval<'a> {
         self.state.lock().borrow_mut().ls.clear();
         let mut counter = 1;
         out.o(format!("{:>3} {:>36} {:>4}       {}", "", "Password", "Len", "Name"));
+        let mut passwords = vec![];
         for num in (encpwds.len() - min(genpwds.len(), num))..encpwds.len() {
             let (pwd, pass) = (encpwds[num].0.clone(), encpwds[num].1.to_string());
             let key = Radix::new(counter, 36).unwrap().to_string();
             counter += 1;
             self.state.lock().borrow_mut().ls.insert(key.clone(), pwd.clone());
-            out.o(format!("{:>3} {:>36} {:>4} {}", key, pass, pass.len(), pwd.lock().borrow().to_string()));
+            let line = self.ls_line(&pwd);
+            out.o(format!("{:>3} {:>36} {:>4} {}", key, pass, pass.len(), line));
+            let entry = Entry::new(&key, &pwd.lock().borrow());
+            passwords.push(GenRow { password: pass, entry });
+        }
+        out.set_result(LKResult::Gen { passwords });
+    }
+}
+
+const MAX_GEN_NAMES: usize = 1000;
+
+/// Expand the gen placeholders in a name, every combination of the enumerated ones becomes a name:
+/// `{n}`, `{nn}`, ... count from 1 like trailing G+, `{yyyy}` is one of the last ten years, `{mm}` a
+/// month, `{rand:N}` are N random digits and `{alpha:N}` N random lowercase letters.
+fn expand_placeholders(name: &str) -> Result<Vec<String>, String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"\{([a-z]+)(?::([0-9]+))?\}").unwrap();
+    }
+    let caps = match RE.captures(name) {
+        Some(c) => c,
+        None => return Ok(vec![name.to_string()]),
+    };
+    let (whole, kind) = (caps.get(0).unwrap(), &caps[1]);
+    let width = caps.get(2).map(|w| w.as_str().parse::<u32>().unwrap_or(u32::MAX));
+    let values: Vec<String> = match (kind, width) {
+        (n, None) if n.chars().all(|c| c == 'n') && n.len() < 5 => {
+            (1..10_u32.pow(n.len() as u32)).map(|v| v.to_string()).collect()
+        }
+        ("yyyy", None) => {
+            let year = Date::now().year();
+            (year - 9..=year).map(|y| y.to_string()).collect()
+        }
+        ("mm", None) => (1..=12).map(|m| format!("{:02}", m)).collect(),
+        ("rand", Some(w)) if (1..=9).contains(&w) => {
+            vec![format!("{:0w$}", rnd::range(0, 10_u32.pow(w)), w = w as usize)]
+        }
+        ("alpha", Some(w)) if (1..=32).contains(&w) => {
+            vec![(0..w).map(|_| char::from(b'a' + rnd::range(0, 26) as u8)).collect()]
+        }
+        _ => {
+            return Err(format!(
+                "unknown placeholder {}, use {{n}}, {{yyyy}}, {{mm}}, {{rand:N}} or {{alpha:N}}",
+                whole.as_str()
+            ))
+        }
+    };
+    let (head, tail) = (&name[..whole.start()], &name[whole.end()..]);
+    let mut names = vec![];
+    for rest in expand_placeholders(tail)? {
+        for value in &values {
+            names.push(format!("{}{}{}", head, value, rest));
+        }
+        if names.len() > MAX_GEN_NAMES {
+            return Err(format!("the placeholders expand to more than {} names", MAX_GEN_NAMES));
+        }
+    }
+    Ok(names)
+}
+
+/// Replace the `set KEY` line of the init file, or append one, so the setting survives the session.
+fn save_setting(file: &Path, key: &str, value: &str) -> std::io::Result<()> {
+    let script = match fs::read_to_string(file) {
+        Ok(s) => s,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let line = format!("set {} {}", key, quote_name(value));
+    let mut found = false;
+    let mut lines: Vec<String> = vec![];
+    for l in script.lines() {
+        if l.split_whitespace().take(2).eq(["set", key]) {
+            if !found {
+                lines.push(line.clone());
+            }
+            found = true;
+        } else {
+            lines.push(l.to_string());
+        }
+    }
+    if !found {
+        lines.push(line);
+    }
+    let new_script = lines.join("\n") + "\n";
+    if new_script != script {
+        fs::write(file, new_script)?;
+    }
+    Ok(())
+}
+
+fn load_correct(file: &Path) -> std::io::Result<HashSet<String>> {
+    let file = fs::File::open(file)?;
+    let reader = BufReader::new(file);
+    let mut lines = HashSet::new();
+    for line in reader.lines() {
+        lines.insert(line?.trim().to_owned());
+    }
+    Ok(lines)
+}
+
+fn correct_hash(name: &str, pass: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(name);
+    sha1.update(pass);
+    format!("{:x}", sha1.finalize())
+}
+
+/// All entries as script lines which recreate them, sorted by name.
+fn dump_script(lk: &LK) -> Vec<String> {
+    let defaults_line = |name: &str| {
+        lk.defaults.get(name).map(|fields| {
+            let fields = fields.iter().map(|f| f.to_string()).collect::<Vec<String>>();
+            format!("defaults {} {}", quote_name(name), fields.join(" "))
+        })
+    };
+    let mut vals = lk.db.values().cloned().collect::<Vec<PasswordRef>>();
+    vals.sort_by(|a, b| a.lock().borrow().name.cmp(&b.lock().borrow().name));
+    let mut lines = defaults_line("/").into_iter().collect::<Vec<String>>();
+    for pwd in vals {
+        let pwd = pwd.lock();
+        let pwd = pwd.borrow();
+        lines.push(format!("add {}", pwd.to_string()));
+        if let Some(alias) = &pwd.alias {
+            lines.push(format!("rename {} {}", quote_name(&pwd.name), quote_name(alias)));
+        }
+        lines.extend(defaults_line(&pwd.name));
+    }
+    lines
+}
+
+/// Run a shell command line with `input` on its stdin, errors are reported to `out`.
+fn run_shell(out: &LKOut, command: &str, input: &str) -> Option<String> {
+    let (cmd, args) = match get_cmd_args_from_command(command) {
+        Ok(c) => c,
+        Err(e) => {
+            out.fail(LKErr::Error(format!("failed to parse command {:?}: {}", command, e)));
+            return None;
+        }
+    };
+    match call_cmd_with_input(&cmd, &args, input) {
+        Ok(o) => Some(o),
+        Err(e) => {
+            out.fail(LKErr::Io(format!("failed to execute command {}: {}", cmd, e)));
+            None
         }
     }
 }
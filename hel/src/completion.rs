@@ -0,0 +1,435 @@
+use crate::lk::LK;
+use crate::parser::{partial_parse, COMMANDS_HELP};
+use crate::structs::Settings;
+use crate::utils::quote_name;
+
+const MODES: [&str; 10] = ["R", "UR", "N", "UN", "C", "H", "UH", "B", "UB", "D"];
+const NAME_COMMANDS: [&str; 13] = [
+    "keep",
+    "mv",
+    "rm",
+    "comment",
+    "edit",
+    "rename",
+    "show",
+    "enc",
+    "type",
+    "pass",
+    "unpass",
+    "correct",
+    "uncorrect",
+];
+const EDIT_FIELDS: [&str; 6] = ["prefix=", "len=", "mode=", "seq=", "date=", "comment="];
+const PATH_COMMANDS: [&str; 2] = ["source", "save"];
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Candidate {
+    pub display: String,
+    pub replacement: String,
+}
+
+impl Candidate {
+    fn new(display: String, replacement: String) -> Self {
+        Self { display, replacement }
+    }
+
+    fn same(text: String) -> Self {
+        Self::new(text.clone(), text)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Hint {
+    pub display: String,
+    pub completion: Option<String>,
+}
+
+#[derive(PartialEq, Debug)]
+pub struct Word {
+    pub start: usize,
+    pub text: String,
+    pub closed: bool,
+}
+
+/// Split a command line into words, a quoted word counts as one word with the quotes and escapes removed.
+pub fn split_words(line: &str) -> Vec<Word> {
+    let mut words = vec![];
+    let mut chars = line.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() || c == '\u{200B}' {
+            continue;
+        }
+        let mut text = String::new();
+        let mut closed = true;
+        if c == '"' {
+            closed = false;
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '\\' if matches!(chars.peek(), Some((_, '"' | '\\'))) => text.push(chars.next().unwrap().1),
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    _ => text.push(c),
+                }
+            }
+        } else {
+            text.push(c);
+            while let Some((_, c)) = chars.peek() {
+                if c.is_whitespace() || *c == '\u{200B}' {
+                    break;
+                }
+                text.push(*c);
+                chars.next();
+            }
+        }
+        words.push(Word { start, text, closed });
+    }
+    words
+}
+
+/// Find where the command under the cursor starts: after the last `;`, `|` or `>` outside
+/// quotes. Returns the offset and the operator if it was a pipe or a redirection.
+pub fn segment(line: &str) -> (usize, Option<char>) {
+    let (mut start, mut op, mut quoted, mut escaped, mut prev) = (0, None, false, false, ' ');
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => (start, op) = (i + 1, None),
+            '|' | '>' if !quoted && (prev.is_whitespace() || (c == '>' && prev == '>')) => {
+                (start, op) = (i + 1, Some(c))
+            }
+            _ => (),
+        }
+        prev = c;
+    }
+    (start, op)
+}
+
+/// Complete the word before `pos`, returns the position where the replacement starts and the candidates.
+pub fn complete(lk: &LK, line: &str, pos: usize) -> (usize, Vec<Candidate>) {
+    let (offset, op) = segment(&line[..pos]);
+    let line = &line[offset..pos];
+    let mut words = split_words(line);
+    let in_word = match words.last() {
+        Some(w) => !w.closed || !line.ends_with(|c: char| c.is_whitespace()),
+        None => false,
+    };
+    let (start, current) = if in_word {
+        let w = words.pop().unwrap();
+        (w.start, w.text)
+    } else {
+        (line.len(), "".to_string())
+    };
+    let candidates = match op {
+        Some('|') => vec![],
+        Some(_) => complete_path(&current),
+        None => complete_args(lk, &words.into_iter().map(|w| w.text).collect::<Vec<String>>(), &current),
+    };
+    (offset + start, candidates)
+}
+
+/// Ghost text shown after the cursor: the rest of a unique entry name with its
+/// comment, or the grammar fields expected next.
+pub fn hint(lk: &LK, line: &str, pos: usize) -> Option<Hint> {
+    if pos < line.len() || line.trim().is_empty() {
+        return None;
+    }
+    let name = match segment(line) {
+        (offset, None) => name_hint(lk, &line[offset..]),
+        _ => None,
+    };
+    name.or_else(|| field_hint(line))
+}
+
+fn name_hint(lk: &LK, line: &str) -> Option<Hint> {
+    let words = split_words(line);
+    if words.len() != 2 || (words[1].closed && line.ends_with(|c: char| c.is_whitespace())) {
+        return None;
+    }
+    if !NAME_COMMANDS.contains(&command_keyword(&words[0].text)) {
+        return None;
+    }
+    let (typed, current) = (&line[words[1].start..], &words[1].text);
+    if let Some(pwd) = lk.ls.get(current) {
+        let pwd = pwd.lock();
+        let pwd = pwd.borrow();
+        let comment = pwd.comment.as_ref().map(|c| format!(" {}", c)).unwrap_or_default();
+        return Some(Hint {
+            display: format!("  {}{}", quote_name(&pwd.name), comment),
+            completion: None,
+        });
+    }
+    let mut names = lk.db.keys().filter(|n| n.starts_with(current.as_str()));
+    let name = match (names.next(), names.next()) {
+        (Some(n), None) => n,
+        _ => return None,
+    };
+    let quoted = quote_name(name);
+    let rest = quoted.strip_prefix(typed).unwrap_or("").to_string();
+    let comment = match &lk.db[name].lock().borrow().comment {
+        Some(c) => format!("  {}", c),
+        None => "".to_string(),
+    };
+    if rest.is_empty() && comment.is_empty() {
+        return None;
+    }
+    Some(Hint {
+        display: format!("{}{}", rest, comment),
+        completion: if rest.is_empty() { None } else { Some(rest) },
+    })
+}
+
+fn field_hint(line: &str) -> Option<Hint> {
+    let fields = |expected: Vec<&'static str>| {
+        expected
+            .into_iter()
+            .filter(|e| e.chars().all(|c| c.is_ascii_uppercase()) && *e != "SPACE" && *e != "EOF")
+            .collect::<Vec<&str>>()
+    };
+    let (sep, expected) = match partial_parse(line) {
+        Some(p) if p.offset == line.len() && !fields(p.expected.clone()).is_empty() => ("", fields(p.expected)),
+        Some(p) if p.offset == line.len() && p.expected.contains(&"SPACE") => {
+            match partial_parse(&format!("{} ", line)) {
+                Some(p) if p.offset == line.len() + 1 => (" ", fields(p.expected)),
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    if expected.is_empty() {
+        return None;
+    }
+    Some(Hint {
+        display: format!("{}<{}>", sep, expected.join("|")),
+        completion: None,
+    })
+}
+
+/// Colour the part of the line which does not parse in red and the command keyword in bold.
+pub fn highlight(line: &str) -> String {
+    match partial_parse(line) {
+        Some(p) if p.offset < line.len() => format!("{}\x1b[1;31m{}\x1b[0m", &line[..p.offset], &line[p.offset..]),
+        Some(_) => line.to_string(),
+        None => match split_words(line).first() {
+            Some(w) if w.text.starts_with('#') => format!("\x1b[2m{}\x1b[0m", line),
+            Some(w) => {
+                let end = w.start + w.text.len();
+                format!("{}\x1b[1m{}\x1b[0m{}", &line[..w.start], &line[w.start..end], &line[end..])
+            }
+            None => line.to_string(),
+        },
+    }
+}
+
+fn complete_args(lk: &LK, args: &[String], current: &str) -> Vec<Candidate> {
+    let cmd = match args.first() {
+        Some(c) => command_keyword(c),
+        None => return complete_commands(current),
+    };
+    if matches!((cmd, args.get(1).map(|a| a.as_str())), ("edit" | "foreach", Some("-y" | "-n")) | ("type", Some("-u")))
+    {
+        let args = [&args[..1], &args[2..]].concat();
+        return complete_args(lk, &args, current);
+    }
+    match (cmd, args.len()) {
+        ("pb", _) => complete_args(lk, &args[1..], current),
+        ("foreach", n) if n >= 2 => complete_args(lk, &args[2..], current),
+        ("edit", n) if n >= 2 => complete_edit_fields(current, &EDIT_FIELDS),
+        ("defaults", n) if n >= 2 => complete_edit_fields(current, &EDIT_FIELDS[..4]),
+        ("help", 1) => complete_commands(current),
+        ("set" | "get", 1) => complete_words(current, &Settings::KEYS),
+        ("set", 2) => match args[1].as_str() {
+            "mode" => complete_modes(current),
+            "sort" => complete_words(current, &["name", "date"]),
+            "date" => complete_words(current, &["iso", "age"]),
+            "output" => complete_words(current, &["text", "json"]),
+            "clip" => complete_words(current, &["clipboard", "primary"]),
+            _ => vec![],
+        },
+        (c, _) if PATH_COMMANDS.contains(&c) => complete_path(current),
+        ("add" | "gen", n) if n >= 2 && !args[2..].iter().any(|a| is_mode(a)) => complete_modes(current),
+        ("mv", 2) | ("pass" | "unpass" | "cd" | "defaults" | "undefaults", 1) => {
+            let mut candidates = complete_names(lk, current);
+            if "/".starts_with(current) {
+                candidates.insert(0, Candidate::same("/".to_string()));
+            }
+            candidates
+        }
+        (c, 1) if NAME_COMMANDS.contains(&c) => complete_names(lk, current),
+        _ => vec![],
+    }
+}
+
+fn command_keyword(word: &str) -> &str {
+    if word.starts_with("gen") && word[3..].chars().all(|c| c.is_ascii_digit()) {
+        "gen"
+    } else {
+        word
+    }
+}
+
+fn complete_commands(current: &str) -> Vec<Candidate> {
+    COMMANDS_HELP
+        .iter()
+        .filter(|h| h.name.starts_with(current))
+        .map(|h| Candidate::same(h.name.to_string()))
+        .collect()
+}
+
+fn complete_names(lk: &LK, current: &str) -> Vec<Candidate> {
+    let aliases = lk.db.values().filter_map(|p| p.lock().borrow().alias.clone());
+    let mut names = lk.db.keys().cloned().chain(aliases).filter(|n| n.starts_with(current)).collect::<Vec<String>>();
+    names.sort();
+    let mut keys = lk.ls.keys().filter(|k| k.starts_with(current)).cloned().collect::<Vec<String>>();
+    keys.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+    let mut candidates = names.into_iter().map(|n| Candidate::same(quote_name(&n))).collect::<Vec<Candidate>>();
+    for key in keys {
+        let display = format!("{}={}", key, lk.ls[&key].lock().borrow().name);
+        candidates.push(Candidate::new(display, key));
+    }
+    candidates
+}
+
+fn complete_words(current: &str, words: &[&str]) -> Vec<Candidate> {
+    words.iter().filter(|w| w.starts_with(current)).map(|w| Candidate::same(w.to_string())).collect()
+}
+
+fn complete_edit_fields(current: &str, fields: &[&str]) -> Vec<Candidate> {
+    if let Some(mode) = current.strip_prefix("mode=") {
+        return complete_modes(mode)
+            .into_iter()
+            .map(|c| Candidate::same(format!("mode={}", c.replacement)))
+            .collect();
+    }
+    complete_words(current, fields)
+}
+
+fn is_mode(word: &str) -> bool {
+    let mode = word.trim_start_matches(|c: char| c.is_ascii_digit());
+    MODES.iter().any(|m| m.eq_ignore_ascii_case(mode))
+}
+
+fn complete_modes(current: &str) -> Vec<Candidate> {
+    let mode_start = current.find(|c: char| !c.is_ascii_digit()).unwrap_or(current.len());
+    let (length, mode) = current.split_at(mode_start);
+    MODES
+        .iter()
+        .filter(|m| m.len() >= mode.len() && m[..mode.len()].eq_ignore_ascii_case(mode))
+        .map(|m| Candidate::same(format!("{}{}", length, m)))
+        .collect()
+}
+
+fn complete_path(current: &str) -> Vec<Candidate> {
+    let (dir, file) = match current.rfind('/') {
+        Some(i) => current.split_at(i + 1),
+        None => ("", current),
+    };
+    let expanded = match dir {
+        "" => ".".to_string(),
+        d => shellexpand::tilde(d).into_owned(),
+    };
+    let entries = match std::fs::read_dir(expanded) {
+        Ok(e) => e,
+        Err(_) => return vec![],
+    };
+    let mut candidates = vec![];
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(file) || (name.starts_with('.') && !file.starts_with('.')) {
+            continue;
+        }
+        let suffix = if entry.path().is_dir() { "/" } else { "" };
+        candidates.push(Candidate::new(format!("{}{}", name, suffix), format!("{}{}{}", dir, name, suffix)));
+    }
+    candidates.sort_by(|a, b| a.display.cmp(&b.display));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::password::Password;
+    use crate::structs::Mode;
+    use crate::utils::date::Date;
+
+    fn replacements(lk: &LK, line: &str) -> (usize, Vec<String>) {
+        let (start, candidates) = complete(lk, line, line.len());
+        (start, candidates.into_iter().map(|c| c.replacement).collect())
+    }
+
+    #[test]
+    fn complete_test() {
+        let mut lk = LK::new();
+        for name in ["github", "gitlab", "my bank"] {
+            let pwd = Password::new(None, name.to_string(), None, Mode::Regular, 99, Date::new(2022, 12, 30), None);
+            lk.db.insert(name.to_string(), Password::from_password(pwd));
+        }
+        lk.ls.insert("1".to_string(), lk.db["gitlab"].clone());
+
+        assert_eq!(
+            replacements(&lk, "co"),
+            (0, vec!["comment".to_string(), "correct".to_string(), "config".to_string()])
+        );
+        assert_eq!(replacements(&lk, "  enc git"), (6, vec!["github".to_string(), "gitlab".to_string()]));
+        assert_eq!(replacements(&lk, "enc my"), (4, vec!["\"my bank\"".to_string()]));
+        assert_eq!(replacements(&lk, "rm \"my b"), (3, vec!["\"my bank\"".to_string()]));
+        assert_eq!(replacements(&lk, "enc 1"), (4, vec!["1".to_string()]));
+        assert_eq!(replacements(&lk, "pb enc gith"), (7, vec!["github".to_string()]));
+        assert_eq!(
+            replacements(&lk, "mv github "),
+            (10, vec!["/", "github", "gitlab", "\"my bank\"", "1"].into_iter().map(|s| s.to_string()).collect())
+        );
+        assert_eq!(
+            replacements(&lk, "add foo 20u"),
+            (8, vec!["20UR".to_string(), "20UN".to_string(), "20UH".to_string(), "20UB".to_string()])
+        );
+        assert_eq!(replacements(&lk, "gen5 foo c"), (9, vec!["C".to_string()]));
+        assert_eq!(replacements(&lk, "add foo R 9"), (10, vec![]));
+        assert_eq!(replacements(&lk, "help qu"), (5, vec!["quit".to_string()]));
+        assert_eq!(replacements(&lk, "ls gi"), (3, vec![]));
+        assert_eq!(replacements(&lk, "edit -y git"), (8, vec!["github".to_string(), "gitlab".to_string()]));
+        assert_eq!(replacements(&lk, "edit github s"), (12, vec!["seq=".to_string()]));
+        assert_eq!(
+            replacements(&lk, "edit github mode=u"),
+            (12, vec!["mode=UR", "mode=UN", "mode=UH", "mode=UB"].into_iter().map(|s| s.to_string()).collect())
+        );
+        assert_eq!(replacements(&lk, "ls; enc gitl"), (8, vec!["gitlab".to_string()]));
+        assert_eq!(replacements(&lk, "enc github | qrenc"), (13, vec![]));
+    }
+
+    #[test]
+    fn hint_test() {
+        let mut lk = LK::new();
+        for (name, comment) in [("github", Some("me@example.com")), ("gitlab", None), ("my bank", None)] {
+            let pwd = Password::new(None, name.to_string(), None, Mode::Regular, 99, Date::new(2022, 12, 30), None);
+            let pwd = Password::from_password(pwd);
+            pwd.lock().borrow_mut().comment = comment.map(|c| c.to_string());
+            lk.db.insert(name.to_string(), pwd);
+        }
+        lk.ls.insert("1".to_string(), lk.db["github"].clone());
+        let hint = |line: &str| hint(&lk, line, line.len());
+
+        assert_eq!(hint("enc gith").unwrap().display, "ub  me@example.com");
+        assert_eq!(hint("enc gith").unwrap().completion, Some("ub".to_string()));
+        assert_eq!(hint("enc github").unwrap().display, "  me@example.com");
+        assert_eq!(hint("enc \"my").unwrap().completion, Some(" bank\"".to_string()));
+        assert_eq!(hint("enc 1").unwrap().display, "  github me@example.com");
+        assert_eq!(hint("enc git"), None);
+        assert_eq!(hint("add name 20").unwrap().display, "<MODE>");
+        assert_eq!(hint("add name R 99 ").unwrap().display, "<DATE>");
+        assert_eq!(hint("enc").unwrap().display, " <NAME>");
+        assert_eq!(hint("enc x y"), None);
+        assert_eq!(hint("enc x").map(|h| h.display), None);
+    }
+
+    #[test]
+    fn highlight_test() {
+        assert_eq!(highlight("enc x"), "\x1b[1menc\x1b[0m x");
+        assert_eq!(highlight("enc x y"), "enc x \x1b[1;31my\x1b[0m");
+        assert_eq!(highlight("enc"), "enc");
+        assert_eq!(highlight("# note"), "\x1b[2m# note\x1b[0m");
+    }
+}
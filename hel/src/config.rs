@@ -0,0 +1,244 @@
+use crate::structs::LKErr;
+use crate::utils::{did_you_mean, home};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where a configuration value came from.
+#[derive(PartialEq, Debug, Clone)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Profile(PathBuf, String),
+    Env(&'static str),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(file) => write!(f, "{}", file.display()),
+            Source::Profile(file, profile) => write!(f, "{} [profiles.{}]", file.display(), profile),
+            Source::Env(var) => write!(f, "${}", var),
+        }
+    }
+}
+
+/// The config file, `$XDG_CONFIG_HOME/hel/config.toml` unless `$HEL_CONFIG` points elsewhere, with the
+/// `HEL_*` environment variables on top. A profile is picked with `profile = "NAME"` or `$HEL_PROFILE`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Config {
+    pub file: PathBuf,
+    pub profile: Option<(String, Source)>,
+    pub values: Vec<(&'static str, Option<String>, Source)>,
+    pub errors: Vec<String>,
+}
+
+impl Config {
+    pub const KEYS: [&'static str; 6] = ["history", "init", "correct", "dump", "pb", "prompt"];
+
+    pub fn env_var(key: &str) -> &'static str {
+        match key {
+            "history" => "HEL_HISTORY",
+            "init" => "HEL_INIT",
+            "correct" => "HEL_CORRECT",
+            "dump" => "HEL_DUMP",
+            "pb" => "HEL_PB",
+            "prompt" => "HEL_PROMPT",
+            _ => "",
+        }
+    }
+
+    fn default_value(key: &str) -> Option<String> {
+        let file = |name: &str| Some(home::dir().join(name).display().to_string());
+        match key {
+            "history" => file(".hel_history"),
+            "init" => file(".helrc"),
+            "correct" => file(".hel_correct"),
+            "dump" => file(".hel_dump"),
+            "prompt" => Some("> ".to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn default_file() -> PathBuf {
+        match std::env::var("HEL_CONFIG") {
+            Ok(v) => PathBuf::from(shellexpand::full(&v).map(|v| v.into_owned()).unwrap_or(v)),
+            _ => match std::env::var("XDG_CONFIG_HOME") {
+                Ok(v) if !v.is_empty() => PathBuf::from(v),
+                _ => home::dir().join(".config"),
+            }
+            .join("hel")
+            .join("config.toml"),
+        }
+    }
+
+    /// Reads the config file and the environment, problems end up in `errors`.
+    pub fn load() -> Self {
+        let file = Self::default_file();
+        let text = if file.exists() {
+            match std::fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(err) => {
+                    let mut config = Self::parse("", &file, |v| std::env::var(v).ok());
+                    config.errors.push(format!("failed to read config file {}: {}", file.display(), err));
+                    return config;
+                }
+            }
+        } else {
+            "".to_string()
+        };
+        Self::parse(&text, &file, |v| std::env::var(v).ok())
+    }
+
+    pub fn parse(text: &str, file: &Path, env: impl Fn(&str) -> Option<String>) -> Self {
+        let mut config = Self {
+            file: file.to_path_buf(),
+            profile: None,
+            values: Self::KEYS.iter().map(|k| (*k, Self::default_value(k), Source::Default)).collect(),
+            errors: vec![],
+        };
+        let table = match text.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(err) => {
+                config.errors.push(format!("failed to parse {}: {}", file.display(), err.message()));
+                return config;
+            }
+        };
+
+        let mut profiles = toml::Table::new();
+        for (key, value) in table.iter() {
+            match (key.as_str(), value) {
+                ("profile", toml::Value::String(name)) => {
+                    config.profile = Some((name.to_string(), Source::File(file.to_path_buf())))
+                }
+                ("profiles", toml::Value::Table(t)) => profiles = t.clone(),
+                ("profile", _) => config.errors.push(format!("profile in {} must be a string", file.display())),
+                ("profiles", _) => config.errors.push(format!("profiles in {} must be a table", file.display())),
+                _ => config.set(key, value, Source::File(file.to_path_buf())),
+            }
+        }
+        if let Some(name) = env("HEL_PROFILE") {
+            config.profile = Some((name, Source::Env("HEL_PROFILE")));
+        }
+        if let Some((name, _)) = config.profile.clone() {
+            match profiles.get(&name) {
+                Some(toml::Value::Table(t)) => {
+                    for (key, value) in t.iter() {
+                        config.set(key, value, Source::Profile(file.to_path_buf(), name.to_string()));
+                    }
+                }
+                Some(_) => config.errors.push(format!("profiles.{} in {} must be a table", name, file.display())),
+                None => {
+                    let mut msg = format!("profile {} not found in {}", name, file.display());
+                    if let Some(s) = did_you_mean(&name, profiles.keys().map(|k| k.as_str())) {
+                        msg = format!("{}, did you mean {}?", msg, s);
+                    }
+                    config.errors.push(msg);
+                }
+            }
+        }
+        for key in Self::KEYS {
+            if let Some(value) = env(Self::env_var(key)) {
+                config.set(key, &toml::Value::String(value), Source::Env(Self::env_var(key)));
+            }
+        }
+        config
+    }
+
+    fn set(&mut self, key: &str, value: &toml::Value, source: Source) {
+        let entry = match self.values.iter_mut().find(|(k, _, _)| *k == key) {
+            Some(entry) => entry,
+            None => {
+                let mut msg = format!("unknown key {} in {}", key, source);
+                match did_you_mean(key, Self::KEYS.into_iter()) {
+                    Some(s) => msg = format!("{}, did you mean {}?", msg, s),
+                    None => msg = format!("{}, use one of {}", msg, Self::KEYS.join(", ")),
+                }
+                self.errors.push(msg);
+                return;
+            }
+        };
+        match value {
+            toml::Value::String(s) if s.is_empty() && key != "prompt" => {
+                self.errors.push(format!("{} in {} must not be empty", key, source))
+            }
+            toml::Value::String(s) => {
+                entry.1 = Some(s.to_string());
+                entry.2 = source;
+            }
+            _ => self.errors.push(format!("{} in {} must be a string", key, source)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.iter().find(|(k, _, _)| *k == key).and_then(|(_, v, _)| v.as_deref())
+    }
+
+    pub fn source(&self, key: &str) -> Option<&Source> {
+        self.values.iter().find(|(k, _, _)| *k == key).map(|(_, _, s)| s)
+    }
+
+    /// A file setting with `~` and variables expanded.
+    pub fn path(&self, key: &str) -> Box<Path> {
+        let value = self.get(key).unwrap_or_default();
+        Path::new(shellexpand::full(value).map(|v| v.into_owned()).unwrap_or(value.to_string()).as_str())
+            .to_path_buf()
+            .into_boxed_path()
+    }
+
+    /// The validation errors, one line each.
+    pub fn check(&self) -> Result<(), Vec<LKErr>> {
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(self.errors.iter().map(|e| LKErr::Config(e.to_string())).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |v| vars.iter().find(|(k, _)| *k == v).map(|(_, x)| x.to_string())
+    }
+
+    #[test]
+    fn config_profiles_test() {
+        let file = Path::new("/cfg/config.toml");
+        let text = "profile = \"work\"\ninit = \"/a/helrc\"\nprompt = \"hel> \"\n\n\
+                    [profiles.work]\ninit = \"/b/helrc\"\n\n[profiles.home]\npb = \"wl-copy\"\n";
+        let config = Config::parse(text, file, env(&[]));
+        assert_eq!(config.errors, Vec::<String>::new());
+        assert_eq!(config.get("init"), Some("/b/helrc"));
+        assert_eq!(config.source("init"), Some(&Source::Profile(file.to_path_buf(), "work".to_string())));
+        assert_eq!(config.get("prompt"), Some("hel> "));
+        assert_eq!(config.source("prompt").unwrap().to_string(), "/cfg/config.toml");
+        assert_eq!(config.get("pb"), None);
+        assert_eq!(config.source("dump"), Some(&Source::Default));
+
+        let config = Config::parse(text, file, env(&[("HEL_PROFILE", "home"), ("HEL_INIT", "/c/helrc")]));
+        assert_eq!(config.get("pb"), Some("wl-copy"));
+        assert_eq!(config.get("init"), Some("/c/helrc"));
+        assert_eq!(config.source("init").unwrap().to_string(), "$HEL_INIT");
+    }
+
+    #[test]
+    fn config_errors_test() {
+        let file = Path::new("/cfg/config.toml");
+        let config = Config::parse("histroy = \"x\"\ndump = 1\nprofile = \"wrok\"\n[profiles.work]\n", file, env(&[]));
+        assert_eq!(
+            config.errors,
+            vec![
+                "dump in /cfg/config.toml must be a string",
+                "unknown key histroy in /cfg/config.toml, did you mean history?",
+                "profile wrok not found in /cfg/config.toml, did you mean work?",
+            ]
+        );
+        assert_eq!(config.get("dump"), Config::default_value("dump").as_deref());
+        let config = Config::parse("init = [", file, env(&[]));
+        assert_eq!(config.errors.len(), 1);
+        assert!(config.errors[0].starts_with("failed to parse /cfg/config.toml"));
+        assert_eq!(config.check().unwrap_err()[0].exit_code(), 7);
+    }
+}
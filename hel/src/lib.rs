@@ -5,10 +5,16 @@ extern crate lazy_static;
 extern crate scopeguard;
 extern crate num_integer;
 
+pub mod autotype;
+pub mod clipboard;
 pub mod commands;
+pub mod completion;
+pub mod config;
 pub mod lk;
+pub mod output;
 pub mod parser;
 pub mod password;
+pub mod query;
 pub mod repl;
 pub mod skey;
 pub mod structs;
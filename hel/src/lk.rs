@@ -1,4 +1,7 @@
-use crate::password::{fix_password_recursion, Name, PasswordRef};
+use crate::config::Config;
+use crate::password::{fix_password_recursion, Name, Password, PasswordRef};
+use crate::structs::{EditField, Settings, CONFIG};
+use crate::utils::{normalize_name, quote_name, unquote_name};
 use parking_lot::ReentrantMutex;
 use regex::{Captures, Regex};
 use std::cell::RefCell;
@@ -12,6 +15,10 @@ pub struct LK {
     pub db: HashMap<Name, PasswordRef>,
     pub ls: HashMap<String, PasswordRef>,
     pub secrets: HashMap<Name, String>,
+    pub cwd: Option<PasswordRef>,
+    pub defaults: HashMap<Name, Vec<EditField>>,
+    pub settings: Settings,
+    pub config: Config,
 }
 
 impl LK {
@@ -20,12 +27,86 @@ impl LK {
             db: HashMap::new(),
             ls: HashMap::new(),
             secrets: HashMap::new(),
+            cwd: None,
+            defaults: HashMap::new(),
+            settings: Settings::default(),
+            config: CONFIG.clone(),
         }
     }
 
+    pub fn get_alias(&self, alias: &str) -> Option<PasswordRef> {
+        self.db.values().find(|p| p.lock().borrow().alias.as_deref() == Some(alias)).cloned()
+    }
+
+    /// Apply the defaults of `/` and of all folders above the entry, the nearest folder wins.
+    pub fn inherit_defaults(&self, pwd: &mut Password) {
+        let mut folders = vec![];
+        let mut folder = pwd.parent.clone();
+        while let Some(f) = folder {
+            folders.push(f.lock().borrow().name.to_string());
+            folder = f.lock().borrow().parent.clone();
+        }
+        folders.push("/".to_string());
+        for name in folders.iter().rev() {
+            for field in self.defaults.get(name).into_iter().flatten() {
+                pwd.edit(field);
+            }
+        }
+    }
+
+    /// Path of a folder from the top, like `/bank/pin`, `None` is the top folder `/`.
+    pub fn path(folder: &Option<PasswordRef>) -> String {
+        let mut names = vec![];
+        let mut folder = folder.clone();
+        while let Some(f) = folder {
+            names.push(quote_name(&f.lock().borrow().name));
+            folder = f.lock().borrow().parent.clone();
+        }
+        names.reverse();
+        format!("/{}", names.join("/"))
+    }
+
+    /// Resolve a folder path like `/bank/pin`, `pin/card` or `..` relative to the current
+    /// folder. Returns `Some(None)` for the top folder `/` and `None` if nothing matches.
+    pub fn resolve(&self, path: &str) -> Option<Option<PasswordRef>> {
+        let mut folder = if path.starts_with('/') { None } else { self.cwd.clone() };
+        for (i, name) in path.split('/').filter(|n| !n.is_empty()).enumerate() {
+            folder = match name {
+                "." => folder,
+                ".." => folder.and_then(|f| f.lock().borrow().parent.clone()),
+                _ => {
+                    let name = normalize_name(name);
+                    let child = self
+                        .db
+                        .values()
+                        .find(|p| {
+                            let p = p.lock();
+                            let p = p.borrow();
+                            (p.name == name || p.alias.as_deref() == Some(name.as_str()))
+                                && match (&p.parent, &folder) {
+                                    (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                                    (None, None) => true,
+                                    _ => false,
+                                }
+                        })
+                        .cloned();
+                    // names are unique, so the first part of a relative path may be any entry
+                    match child {
+                        Some(c) => Some(c),
+                        None if i == 0 && !path.starts_with('/') => {
+                            Some(self.db.get(&name).cloned().or_else(|| self.get_alias(&name))?)
+                        }
+                        None => return None,
+                    }
+                }
+            };
+        }
+        Some(folder)
+    }
+
     pub fn fix_hierarchy(&self) {
         lazy_static! {
-            static ref RE: Regex = Regex::new(r"\s*\^([!-~]+)").unwrap();
+            static ref RE: Regex = Regex::new(r#"\s*\^("(?:[^"\\]|\\.)+"|\S+)"#).unwrap();
         }
         for db in vec![&self.db, &self.ls] {
             for (_, name) in db {
@@ -35,7 +116,7 @@ impl LK {
                         let mut changed = false;
                         let new = RE
                             .replace(comment.as_str(), |c: &Captures| {
-                                let folder = c[1].to_string();
+                                let folder = normalize_name(&unquote_name(&c[1]));
                                 match self.db.get(&folder) {
                                     Some(entry) => {
                                         name.lock().borrow_mut().parent = Some(entry.clone());
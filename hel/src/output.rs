@@ -0,0 +1,69 @@
+use crate::password::Password;
+use crate::structs::LKErr;
+use serde::Serialize;
+
+/// An entry as listed by ls, ld, find and gen.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub name: String,
+    pub alias: Option<String>,
+    pub prefix: Option<String>,
+    pub length: Option<u32>,
+    pub mode: String,
+    pub seq: u32,
+    pub date: String,
+    pub comment: Option<String>,
+    pub parent: Option<String>,
+}
+
+impl Entry {
+    pub fn new(key: &str, pwd: &Password) -> Self {
+        Self {
+            key: key.to_string(),
+            name: pwd.name.to_string(),
+            alias: pwd.alias.clone(),
+            prefix: pwd.prefix.clone(),
+            length: pwd.length,
+            mode: pwd.mode.to_string(),
+            seq: pwd.seq,
+            date: pwd.date.to_string(),
+            comment: pwd.comment.clone(),
+            parent: pwd.parent.as_ref().map(|p| p.lock().borrow().name.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GenRow {
+    pub password: String,
+    #[serde(flatten)]
+    pub entry: Entry,
+}
+
+/// The result of a command for the JSON output, commands without a typed result give their lines.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LKResult {
+    Entries { entries: Vec<Entry> },
+    Password { name: String, password: String },
+    Gen { passwords: Vec<GenRow> },
+    Lines { lines: Vec<String> },
+    Error { kind: String, code: i32, message: String },
+}
+
+impl From<&LKErr> for LKResult {
+    fn from(error: &LKErr) -> Self {
+        LKResult::Error {
+            kind: error.kind().to_string(),
+            code: error.exit_code(),
+            message: error.to_string().trim_start_matches("error: ").to_string(),
+        }
+    }
+}
+
+impl LKResult {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
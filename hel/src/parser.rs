@@ -1,17 +1,59 @@
 extern crate peg;
 
 use crate::password::Password;
-use crate::structs::{Command, LKErr, Mode};
+use crate::structs::{Command, EditField, LKErr, Mode};
 use crate::utils::date::Date;
+use crate::utils::{did_you_mean, normalize_name};
+
+/// The command line understands `;`, `|` and `>`, scripts like the init file and dumps hold one plain
+/// command per line, so names and comments in them may contain those characters.
+pub mod command_parser {
+    use super::*;
+
+    type Result<T> = std::result::Result<T, peg::error::ParseError<peg::str::LineCol>>;
+
+    pub fn cmd(input: &str) -> Result<Command> {
+        command_grammar::cmd(input, true)
+    }
+
+    pub fn script(input: &str) -> Result<Vec<Command>> {
+        command_grammar::script(input, false)
+    }
+
+    pub fn name(input: &str) -> Result<Password> {
+        command_grammar::name(input, false)
+    }
+}
 
 peg::parser! {
-    pub grammar command_parser() for str {
-        pub rule cmd() -> Command<'input> = c:(info_cmd_list() / mod_cmd_list() / enc_cmd_list() / asides_cmd_list()) { c }
-        pub rule info_cmd_list() -> Command<'input> = space()* c:(ls_cmd() / ld_cmd() / pb_cmd() / save_cmd() / save_def_cmd() / dump_cmd()) { c }
-        pub rule mod_cmd_list() -> Command<'input> = space()* c:(add_cmd() / keep_cmd() / mv_cmd() / rm_cmd() / comment_cmd ()) { c }
-        pub rule asides_cmd_list() -> Command<'input> = space()* c:(help_cmd() / source_cmd() / quit_cmd() / noop_cmd() / error_cmd()) { c }
-        pub rule enc_cmd_list() -> Command<'input> = space()* c:(enc_cmd() / gen_cmd() / pass_cmd() / unpass_cmd() / correct_cmd() / uncorrect_cmd()) { c }
-        pub rule script() -> Vec<Command<'input>> = c:(info_cmd_list() / mod_cmd_list() / enc_cmd_list() / asides_cmd_list()) ++ "\n" { c }
+    grammar command_grammar(ops: bool) for str {
+        pub rule cmd() -> Command = quiet!{ops_on()} c:piped_cmd() ++ ";" {
+            let mut c = c.into_iter().filter(|c| *c != Command::Noop).collect::<Vec<Command>>();
+            match c.len() {
+                0 => Command::Noop,
+                1 => c.pop().unwrap(),
+                _ => Command::Chain(c),
+            }
+        } / single_cmd()
+        rule ops_on() = {? if ops { Ok(()) } else { Err("operators") } }
+        rule single_cmd() -> Command = c:(info_cmd_list() / mod_cmd_list() / enc_cmd_list() / asides_cmd_list()) quiet!{space()*} { c }
+        rule piped_cmd() -> Command = c:single_cmd() p:pipe()* r:redirect()? {?
+            if c == Command::Noop && (!p.is_empty() || r.is_some()) {
+                return Err("command before | or >");
+            }
+            let c = p.into_iter().fold(c, |c, p| Command::Pipe(Box::new(c), p));
+            match r {
+                Some((append, file)) => Ok(Command::Redirect(Box::new(c), file, append)),
+                None => Ok(c),
+            }
+        }
+        rule pipe() -> String = "|" quiet!{space()*} s:shell() { s.trim_end().to_string() }
+        rule redirect() -> (bool, String) = a:$(">>" / ">") quiet!{space()*} f:file() quiet!{space()*} { (a == ">>", f) }
+        rule info_cmd_list() -> Command = quiet!{space()*} c:(ls_cmd() / ld_cmd() / find_cmd() / show_cmd() / tree_cmd() / cd_cmd() / pwd_cmd() / pb_cmd() / save_pipe_cmd() / save_cmd() / save_def_cmd() / dump_cmd()) { c }
+        rule mod_cmd_list() -> Command = quiet!{space()*} c:(add_default_cmd() / add_cmd() / keep_cmd() / mv_cmd() / rm_cmd() / comment_cmd () / edit_cmd() / rename_cmd() / defaults_cmd() / undefaults_cmd() / foreach_cmd()) { c }
+        rule asides_cmd_list() -> Command = quiet!{space()*} c:(help_cmd() / source_cmd() / settings_cmd() / set_cmd() / get_cmd() / config_cmd() / quit_cmd() / noop_cmd() / error_cmd()) { c }
+        rule enc_cmd_list() -> Command = quiet!{space()*} c:(enc_cmd() / type_cmd() / gen_default_cmd() / gen_cmd() / pass_cmd() / unpass_cmd() / correct_cmd() / uncorrect_cmd()) { c }
+        pub rule script() -> Vec<Command> = c:cmd() ++ "\n" { c }
 
         rule space() -> &'input str = s:$(
               " "         // Space (U+0020)
@@ -26,22 +68,35 @@ peg::parser! {
             / "\u{200B}"  // Zero-width space (U+200B)
             / "\u{3000}"  // Ideographic space (U+3000)
         ) { s }
-        rule _() -> &'input str = s:$(space()+) { s }
-        rule comment() -> String = _ c:$([' '..='~']+) { c.to_string() }
-        rule word() -> String = n:$(['!'..='~']+) { n.to_string() }
-        rule num() -> u32 = n:$(['0'..='9']+) {? n.parse().or(Err("not a number")) }
+        rule _() -> &'input str = s:$(quiet!{space()+}) { s } / expected!("SPACE")
+        rule op() = ops_on() (space()* ";" / space()+ (">" / "|" !(space()* ("\n" / ![_]))))
+        rule text() -> &'input str = t:$(quiet!{!(ops_on() (";" / "|" / ">")) (!op() [^'\n'])+}) { t } / expected!("TEXT")
+        rule rest() -> &'input str = t:$(quiet!{[^'\n']+}) { t } / expected!("TEXT")
+        // quote_text marks quoted comments with a backslash, scripts keep other leading quotes as text
+        rule comment() -> String = _ c:(("\\" / ops_on()) q:qword() t:$(text()?) { q + t } / t:text() { t.to_string() }) { c }
+        rule shell() -> &'input str = s:$(quiet!{("\"" ("\\" [_] / [^'"' | '\n'])* "\"" / "'" [^'\'' | '\n']* "'" / [^';' | '|' | '>' | '\n'])+}) { s }
+            / expected!("COMMAND")
+        rule file() -> String = f:quiet!{qword() / bword()} { f } / expected!("FILE")
+        rule filter() -> String = f:quiet!{qword() / bword()} { f } / expected!("FILTER")
+        rule qchar() -> char = "\\" c:['"' | '\\'] { c } / c:[^'"' | '\n'] { c }
+        rule qword() -> String = "\"" s:qchar()* "\"" { s.into_iter().collect() }
+        rule bword() -> String = n:$(!"\"" (!(space() / "\n" / ops_on() ";") [_])+) { n.to_string() }
+        rule word() -> String = w:quiet!{qword() / bword()} { normalize_name(&w) } / expected!("NAME")
+        rule num() -> u32 = n:$(quiet!{['0'..='9']+}) {? n.parse().or(Err("not a number")) } / expected!("NUMBER")
+        rule len() -> u32 = n:quiet!{num()} { n } / expected!("LEN")
+        rule seq() -> u32 = n:quiet!{num()} { n } / expected!("SEQ")
 
-        rule pname() -> Password = &(word() _ word() _ num()? mode() _ num() _ date()) pr:word() _ pn:word() _ pl:num()? pm:mode() _ ps:num() _ pd:date() pc:comment()?
+        rule pname() -> Password = pr:word() _ pn:word() _ pl:len()? pm:mode() _ ps:seq() _ pd:date() pc:comment()?
         { Password::new(Some(pr), pn, pl, pm, ps, pd, pc) }
-        rule jname() -> Password = &(word() _ num()? mode() _ num() _ date()) pn:word() _ pl:num()? pm:mode() _ ps:num() _ pd:date() pc:comment()?
+        rule jname() -> Password = pn:word() _ pl:len()? pm:mode() _ ps:seq() _ pd:date() pc:comment()?
         { Password::new(None, pn, pl, pm, ps, pd, pc) }
-        rule mname() -> Password = &(word() _ word() _ num()? mode() _ date()) pr:word() _ pn:word() _ pl:num()? pm:mode() _ pd:date() pc:comment()?
+        rule mname() -> Password = pr:word() _ pn:word() _ pl:len()? pm:mode() _ pd:date() pc:comment()?
         { Password::new(Some(pr), pn, pl, pm, 99, pd, pc) }
-        rule sname() -> Password = &(word() _ num()? mode() _ date()) pn:word() _ pl:num()? pm:mode() _ pd:date() pc:comment()?
+        rule sname() -> Password = pn:word() _ pl:len()? pm:mode() _ pd:date() pc:comment()?
         { Password::new(None, pn, pl, pm, 99, pd, pc) }
-        rule nname() -> Password = &(word() _ num()? mode()) pn:word() _ pl:num()? pm:mode()
+        rule nname() -> Password = pn:word() _ pl:len()? pm:mode()
         { Password::new(None, pn, pl, pm, 99, Date::now(), None) }
-        rule qname() -> Password = &(word()) pn:word()
+        rule qname() -> Password = pn:word()
         { Password::new(None, pn, None, Mode::NoSpaceCamel, 99, Date::now(), None) }
         pub rule name() -> Password = name:(jname() / pname() / mname() / sname() / nname() / qname())? {?
             match name { Some(n) => Ok(n), None => Err("failed to parse password description") }
@@ -54,7 +109,7 @@ peg::parser! {
             Date::try_new(year, month, day)
         }
         rule cdate() -> Date = "now" { Date::now() }
-        rule date() -> Date = d:(ndate() / cdate()) { d }
+        rule date() -> Date = d:quiet!{ndate() / cdate()} { d } / expected!("DATE")
         rule umode() -> Mode = ("U" / "u") m:$("R" / "r" / "N" / "n" / "H" / "h" / "B" / "b") {?
             match m.to_uppercase().as_str() {
                 "R" => Ok(Mode::RegularUpcase),
@@ -76,41 +131,504 @@ peg::parser! {
                 _ => Err("unknown mode"),
             }
         }
-        rule mode() -> Mode = m:(umode() / rmode()) { m }
+        rule mode() -> Mode = m:quiet!{umode() / rmode()} { m } / expected!("MODE")
 
-        rule noop_cmd() -> Command<'input> = ("#" [' '..='~']*)? { Command::Noop }
-        rule help_cmd() -> Command<'input> = "help" { Command::Help }
-        rule quit_cmd() -> Command<'input> = "quit" { Command::Quit }
-        rule pb_cmd() -> Command<'input> = "pb" _ e:$(([' '..='~'])+) { Command::PasteBuffer(e.to_string()) }
-        rule save_cmd() -> Command<'input> = "save" _ s:$(([' '..='~'])+) { Command::Dump(Some(s.to_string())) }
-        rule save_def_cmd() -> Command<'input> = "save" { Command::Dump(None) }
-        rule dump_cmd() -> Command<'input> = "dump" { Command::Dump(Some("-".to_string())) }
-        rule source_cmd() -> Command<'input> = "source" _ s:$(([' '..='~'])+) { Command::Source(s.to_string()) }
-        rule ls_cmd() -> Command<'input> = "ls" f:comment()? { Command::Ls(f.unwrap_or(".".to_string())) }
-        rule ld_cmd() -> Command<'input> = "ld" f:comment()? { Command::Ld(f.unwrap_or(".".to_string())) }
-        rule add_cmd() -> Command<'input> = "add" _ name:name() { Command::Add(Password::from_password(name)) }
-        rule keep_cmd() -> Command<'input> = "keep" _ name:word() { Command::Keep(name.to_string()) }
-        rule gen_cmd() -> Command<'input> = "gen" n:num()? _ name:name() {
-            Command::Gen(match n { Some(n) => n, None => 10_u32 }, Password::from_password(name))
+        rule noop_cmd() -> Command = ("#" [^'\n']*)? { Command::Noop }
+        rule help_cmd() -> Command = "help" t:(_ t:word() { t })? { Command::Help(t) }
+        rule quit_cmd() -> Command = "quit" { Command::Quit }
+        rule set_cmd() -> Command = "set" _ k:key() _ v:word() { Command::Set(k, v) }
+        rule get_cmd() -> Command = "get" k:(_ k:key() { k })? { Command::Get(k) }
+        rule settings_cmd() -> Command = "settings" { Command::Get(None) }
+        rule config_cmd() -> Command = "config" { Command::Config }
+        rule key() -> String = k:$(quiet!{['a'..='z']+}) { k.to_string() } / expected!("KEY")
+        rule pb_cmd() -> Command = "pb" _ e:text() { Command::PasteBuffer(e.to_string()) }
+        rule save_pipe_cmd() -> Command = "save" _ s:$("|" rest()) { Command::Dump(Some(s.to_string())) }
+        rule save_cmd() -> Command = "save" _ s:text() { Command::Dump(Some(s.to_string())) }
+        rule save_def_cmd() -> Command = "save" { Command::Dump(None) }
+        rule dump_cmd() -> Command = "dump" { Command::Dump(Some("-".to_string())) }
+        rule source_cmd() -> Command = "source" _ s:text() { Command::Source(s.to_string()) }
+        rule ls_cmd() -> Command = "ls" f:comment()? { Command::Ls(f.unwrap_or(".".to_string())) }
+        rule ld_cmd() -> Command = "ld" f:comment()? { Command::Ld(f.unwrap_or(".".to_string())) }
+        rule add_default_cmd() -> Command = "add" _ name:word() &(quiet!{space()*} (";" / "|" / ">" / "\n" / ![_])) {
+            Command::AddDefault(name)
+        }
+        rule add_cmd() -> Command = "add" _ name:name() { Command::Add(Password::from_password(name)) }
+        rule find_cmd() -> Command = "find" _ q:text() { Command::Find(q.trim().to_string()) }
+        rule show_cmd() -> Command = "show" _ name:word() { Command::Show(name) }
+        rule tree_cmd() -> Command = "tree" f:(_ f:word() { f })? { Command::Tree(f) }
+        rule cd_cmd() -> Command = "cd" f:(_ f:word() { f })? { Command::Cd(f) }
+        rule pwd_cmd() -> Command = "pwd" { Command::Pwd }
+        rule keep_cmd() -> Command = "keep" _ name:word() { Command::Keep(name.to_string()) }
+        rule gen_default_cmd() -> Command = "gen" n:num()? _ name:word() &(quiet!{space()*} (";" / "|" / ">" / "\n" / ![_])) {
+            Command::GenDefault(n, name)
+        }
+        rule gen_cmd() -> Command = "gen" n:num()? _ name:name() { Command::Gen(n, Password::from_password(name)) }
+        rule error_cmd() -> Command = "error" _ e:text() { Command::Error(LKErr::Error(e.to_string())) }
+        rule mv_cmd() -> Command = "mv" _ name:word() _ folder:word() { Command::Mv(name, folder) }
+        rule pass_short_cmd() -> Command = "pass" _ name:word() { Command::Pass(name, None) }
+        rule pass_long_cmd() -> Command = "pass" _ name:word() _ pass:rest() { Command::Pass(name, Some(pass.to_string())) }
+        rule pass_cmd() -> Command = p:(pass_long_cmd() / pass_short_cmd()) { p }
+        rule correct_cmd() -> Command = "correct" _ name:word() { Command::Correct(name) }
+        rule uncorrect_cmd() -> Command = "uncorrect" _ name:word() { Command::Uncorrect(name) }
+        rule unpass_cmd() -> Command = "unpass" _ name:word() { Command::UnPass(name) }
+        rule enc_cmd() -> Command = "enc" _ name:word() { Command::Enc(name) }
+        rule type_cmd() -> Command = "type" u:(_ "-u")? _ name:word() { Command::Type(name, u.is_some()) }
+        rule rm_cmd() -> Command = "rm" _ name:word() { Command::Rm(name) }
+        rule comment_cmd() -> Command = "comment" _ name:word() c:comment()? { Command::Comment(name, c) }
+        rule rename_cmd() -> Command = "rename" _ name:word() a:(_ a:word() { a })? { Command::Rename(name, a) }
+        rule default_field() -> EditField = "prefix=" p:word()? { EditField::Prefix(p) }
+            / "len=" l:len()? { EditField::Length(l) }
+            / "mode=" m:mode() { EditField::Mode(m) }
+            / "seq=" s:seq() { EditField::Seq(s) }
+        rule edit_field() -> EditField = default_field()
+            / "date=" d:date() { EditField::Date(d) }
+            / "comment=" c:word()? { EditField::Comment(c) }
+        rule defaults_cmd() -> Command = "defaults" _ name:word() f:(_ f:default_field() { f })* {
+            Command::Defaults(name, f)
+        }
+        rule undefaults_cmd() -> Command = "undefaults" _ name:word() { Command::UnDefaults(name) }
+        rule foreach_cmd() -> Command = "foreach" y:(_ y:("-y" { true } / "-n" { false }) { y })? _ f:filter() _ t:text() {
+            Command::Foreach(f, t.trim_end().to_string(), y)
+        }
+        rule edit_cmd() -> Command = "edit" y:(_ "-y")? _ name:word() f:(_ f:edit_field() { f })+ {
+            Command::Edit(name, f, y.is_some())
         }
-        rule error_cmd() -> Command<'input> = "error" _ e:$(([' '..='~'])+) { Command::Error(LKErr::Error(e)) }
-        rule mv_cmd() -> Command<'input> = "mv" _ name:word() _ folder:word() { Command::Mv(name, folder) }
-        rule pass_short_cmd() -> Command<'input> = "pass" _ name:word() { Command::Pass(name, None) }
-        rule pass_long_cmd() -> Command<'input> = "pass" _ name:word() _ pass:$(([' '..='~'])+) { Command::Pass(name, Some(pass.to_string())) }
-        rule pass_cmd() -> Command<'input> = p:(pass_long_cmd() / pass_short_cmd()) { p }
-        rule correct_cmd() -> Command<'input> = "correct" _ name:word() { Command::Correct(name) }
-        rule uncorrect_cmd() -> Command<'input> = "uncorrect" _ name:word() { Command::Uncorrect(name) }
-        rule unpass_cmd() -> Command<'input> = "unpass" _ name:word() { Command::UnPass(name) }
-        rule enc_cmd() -> Command<'input> = "enc" _ name:word() { Command::Enc(name) }
-        rule rm_cmd() -> Command<'input> = "rm" _ name:word() { Command::Rm(name) }
-        rule comment_cmd() -> Command<'input> = "comment" _ name:word() c:comment()? { Command::Comment(name, c) }
     }
 }
 
+pub struct CommandHelp {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub syntax: &'static [&'static str],
+    pub args: &'static [(&'static str, &'static str)],
+    pub examples: &'static [&'static str],
+}
+
+const NAME_ARG: (&str, &str) =
+    ("NAME", "entry name, any non-space UTF-8 text or a quoted string like \"my bank\" (\\\" and \\\\ escape)");
+const KEY_ARG: (&str, &str) =
+    ("NAME", "entry name or alias, or the short key printed in the first column by ls, ld or gen");
+const PREFIX_ARG: (&str, &str) = ("PREFIX", "optional text put in front of the generated password, e.g. #W9");
+const MODE_ARG: (&str, &str) = (
+    "[LEN]MODE",
+    "optional length followed by the mode letter: R regular words, N words joined with -, \
+     C camel case words, H hex, B base64, D decimal numbers; prefix R, N, H or B with U for \
+     upper case (UR, UN, UH, UB, U is the same as UR)",
+);
+const SEQ_ARG: (&str, &str) = ("SEQ", "sequence number, defaults to 99; change it to rotate the password");
+const DATE_ARG: (&str, &str) = ("DATE", "YYYY-MM-DD (year may be negative or shorter) or the word now");
+const COMMENT_ARG: (&str, &str) = ("COMMENT", "free text up to the end of the line, ^FOLDER sets the parent folder");
+const FILTER_ARG: (&str, &str) = ("REGEX", "regular expression matched against the entry line, name and comment");
+const QUERY_ARG: (&str, &str) = (
+    "QUERY",
+    "FIELD:VALUE, FIELD:~REGEX or FIELD<VALUE (also <=, >, >=) with fields name, alias, prefix, comment, \
+     parent, mode, seq, len and date, or ^FOLDER for the entries anywhere below a folder, combined with and, or, \
+     not and parentheses; words without a field are regular expressions",
+);
+const SETTING_ARG: (&str, &str) = ("KEY", "one of mode, seq, gen, sort, date, output, clip, clear, delay");
+const FILE_ARG: (&str, &str) = ("FILE", "path to a file, ~ and $VARS are expanded");
+
+pub const COMMANDS_HELP: &[CommandHelp] = &[
+    CommandHelp {
+        name: "add",
+        summary: "add a new password entry",
+        syntax: &["add NAME", "add NAME [LEN]MODE", "add [PREFIX] NAME [LEN]MODE [SEQ] DATE [COMMENT]"],
+        args: &[PREFIX_ARG, NAME_ARG, MODE_ARG, SEQ_ARG, DATE_ARG, COMMENT_ARG],
+        examples: &[
+            "add github",
+            "add github 20R",
+            "add ableton89 R 99 2020-12-09 xx.ableton@domain.info https://www.ableton.com",
+            "add #W9 \"my bank\" 12UN 98 now ^banks",
+        ],
+    },
+    CommandHelp {
+        name: "keep",
+        summary: "store an entry listed by gen in the database",
+        syntax: &["keep NAME"],
+        args: &[KEY_ARG],
+        examples: &["keep 3"],
+    },
+    CommandHelp {
+        name: "ls",
+        summary: "list entries sorted by name, or by date with set sort date",
+        syntax: &["ls", "ls REGEX", "ls QUERY"],
+        args: &[FILTER_ARG, QUERY_ARG],
+        examples: &["ls", "ls gmail", "ls ^bank", "ls mode:H seq<50", "ls date<2022-01-01 or comment:~gmail"],
+    },
+    CommandHelp {
+        name: "ld",
+        summary: "list entries sorted by date",
+        syntax: &["ld", "ld REGEX", "ld QUERY"],
+        args: &[FILTER_ARG, QUERY_ARG],
+        examples: &["ld", "ld 2022-"],
+    },
+    CommandHelp {
+        name: "find",
+        summary: "fuzzy search entries, best matches first",
+        syntax: &["find QUERY"],
+        args: &[(
+            "QUERY",
+            "words matched in order of their letters against name, alias, comment, prefix and the ls line, all must match",
+        )],
+        examples: &["find gthb", "find bank 2022"],
+    },
+    CommandHelp {
+        name: "show",
+        summary: "show all fields of an entry, its folders, cached secrets and correct mark",
+        syntax: &["show NAME"],
+        args: &[KEY_ARG],
+        examples: &["show github", "show 1"],
+    },
+    CommandHelp {
+        name: "tree",
+        summary: "show the folder hierarchy with entry counts and unlocked folders",
+        syntax: &["tree", "tree FOLDER"],
+        args: &[("FOLDER", "entry to use as the top of the tree, by name, alias or short key")],
+        examples: &["tree", "tree bank"],
+    },
+    CommandHelp {
+        name: "cd",
+        summary: "change the current folder, ls lists its entries and add puts new entries into it",
+        syntax: &["cd", "cd FOLDER"],
+        args: &[(
+            "FOLDER",
+            "folder name, alias or short key, or a path like /bank/pin, pin/card or .., without it go to the top /",
+        )],
+        examples: &["cd bank", "cd ..", "cd /bank/pin", "cd"],
+    },
+    CommandHelp {
+        name: "pwd",
+        summary: "print the path of the current folder",
+        syntax: &["pwd"],
+        args: &[],
+        examples: &["pwd"],
+    },
+    CommandHelp {
+        name: "mv",
+        summary: "move an entry into a folder, / moves it to the top",
+        syntax: &["mv NAME FOLDER"],
+        args: &[KEY_ARG, ("FOLDER", "name of the entry used as folder, or /")],
+        examples: &["mv github work", "mv 2 /"],
+    },
+    CommandHelp {
+        name: "rename",
+        summary: "set or clear the display alias of an entry, the password stays the same",
+        syntax: &["rename NAME", "rename NAME ALIAS"],
+        args: &[
+            KEY_ARG,
+            ("ALIAS", "new display name, accepted wherever an entry name is; omit it to clear the alias"),
+        ],
+        examples: &["rename ableton89 Ableton", "rename \"my bank\" bank", "rename ableton89"],
+    },
+    CommandHelp {
+        name: "rm",
+        summary: "remove an entry",
+        syntax: &["rm NAME"],
+        args: &[KEY_ARG],
+        examples: &["rm github", "rm 2"],
+    },
+    CommandHelp {
+        name: "comment",
+        summary: "set or clear the comment of an entry",
+        syntax: &["comment NAME", "comment NAME COMMENT"],
+        args: &[KEY_ARG, ("COMMENT", "free text up to the end of the line, omit it to clear the comment")],
+        examples: &["comment github user@example.com", "comment 2"],
+    },
+    CommandHelp {
+        name: "edit",
+        summary: "change fields of an entry, shows the new password before applying",
+        syntax: &["edit [-y] NAME FIELD=VALUE..."],
+        args: &[
+            ("-y", "apply without asking for confirmation"),
+            KEY_ARG,
+            ("FIELD", "one of prefix, len, mode, seq, date or comment"),
+            ("VALUE", "new value in the format of add, empty prefix, len or comment clears it"),
+            ("mode=", "mode letter R, N, C, H, B, D or UR, UN, UH, UB, see help add"),
+            DATE_ARG,
+        ],
+        examples: &["edit github seq=98", "edit -y 2 mode=C len=12 date=now", "edit bank comment=\"new account\""],
+    },
+    CommandHelp {
+        name: "defaults",
+        summary: "show or set the prefix, length, mode and seq that add NAME uses for entries below a folder",
+        syntax: &["defaults FOLDER", "defaults FOLDER FIELD..."],
+        args: &[
+            ("FOLDER", "folder name, alias or short key, / for entries at the top"),
+            ("FIELD", "prefix=PREFIX, len=LEN, mode=MODE or seq=SEQ, nearer folders override the ones above"),
+        ],
+        examples: &["defaults bank", "defaults bank len=6 mode=D", "defaults / mode=N seq=98"],
+    },
+    CommandHelp {
+        name: "undefaults",
+        summary: "remove the defaults of a folder",
+        syntax: &["undefaults FOLDER"],
+        args: &[("FOLDER", "folder name, alias or short key, or /")],
+        examples: &["undefaults bank"],
+    },
+    CommandHelp {
+        name: "foreach",
+        summary: "run a command for every entry matching a filter, shows the commands and asks before running them",
+        syntax: &["foreach [-y|-n] FILTER TEMPLATE"],
+        args: &[
+            ("-y", "run without asking for confirmation"),
+            ("-n", "only show the commands"),
+            ("FILTER", "regular expression or query like ls takes, quote it when it has spaces"),
+            ("TEMPLATE", "hel command where {name}, {mode}, {seq} and {date} are replaced with the fields of each entry"),
+        ],
+        examples: &["foreach mode:R edit {name} mode=C", "foreach ^work enc {name}", "foreach -y \"seq<99 date<2020-01-01\" rm {name}"],
+    },
+    CommandHelp {
+        name: "enc",
+        summary: "print the password of an entry",
+        syntax: &["enc NAME"],
+        args: &[KEY_ARG],
+        examples: &["enc github", "enc 1"],
+    },
+    CommandHelp {
+        name: "type",
+        summary: "type the password into the focused window with wtype, xdotool or ydotool",
+        syntax: &["type NAME", "type -u NAME"],
+        args: &[KEY_ARG, ("-u", "type the user=NAME from the comment, then Tab and the password")],
+        examples: &["type github", "type -u 1"],
+    },
+    CommandHelp {
+        name: "gen",
+        summary: "generate passwords for name variants and list the strongest",
+        syntax: &["gen[COUNT] NAME", "gen[COUNT] [PREFIX] NAME [LEN]MODE [SEQ] DATE [COMMENT]"],
+        args: &[
+            ("COUNT", "number of results to show, defaults to the gen setting (10)"),
+            (
+                "NAME",
+                "entry name, trailing G+ enumerates numbers (nameGG: name1..name99), trailing X+ picks random digits",
+            ),
+            (
+                "{...}",
+                "placeholders anywhere in NAME: {n}, {nn} count like G+, {yyyy} the last ten years, {mm} months, {rand:N} N random digits, {alpha:N} N random letters",
+            ),
+            PREFIX_ARG,
+            MODE_ARG,
+            SEQ_ARG,
+            DATE_ARG,
+            COMMENT_ARG,
+        ],
+        examples: &["gen githubG", "gen5 accountXXX 12R now", "gen github{yyyy}", "gen acct{n}x{mm}"],
+    },
+    CommandHelp {
+        name: "pass",
+        summary: "remember the master password of a folder for this session",
+        syntax: &["pass NAME", "pass NAME PASSWORD"],
+        args: &[KEY_ARG, ("PASSWORD", "the password, asked for interactively when omitted")],
+        examples: &["pass /", "pass work"],
+    },
+    CommandHelp {
+        name: "unpass",
+        summary: "forget a remembered master password",
+        syntax: &["unpass NAME"],
+        args: &[("NAME", "folder name or /")],
+        examples: &["unpass /"],
+    },
+    CommandHelp {
+        name: "correct",
+        summary: "mark the current password of an entry as correct",
+        syntax: &["correct NAME"],
+        args: &[KEY_ARG],
+        examples: &["correct github"],
+    },
+    CommandHelp {
+        name: "uncorrect",
+        summary: "remove the correct mark of an entry",
+        syntax: &["uncorrect NAME"],
+        args: &[KEY_ARG],
+        examples: &["uncorrect github"],
+    },
+    CommandHelp {
+        name: "pb",
+        summary: "run a command and copy its output to the clipboard, which is cleared after clear seconds",
+        syntax: &["pb COMMAND"],
+        args: &[("COMMAND", "any hel command")],
+        examples: &["pb enc github"],
+    },
+    CommandHelp {
+        name: "source",
+        summary: "run hel commands from a file or from the output of a shell command",
+        syntax: &["source FILE", "source SHELL-COMMAND |"],
+        args: &[FILE_ARG, ("SHELL-COMMAND", "command line whose output is read as script")],
+        examples: &["source ~/.helrc", "source gpg -d ~/hel.gpg |"],
+    },
+    CommandHelp {
+        name: "save",
+        summary: "save all entries as a script, to the dump file from the config by default",
+        syntax: &["save", "save FILE", "save | SHELL-COMMAND"],
+        args: &[FILE_ARG, ("SHELL-COMMAND", "command line which gets the script on its input")],
+        examples: &["save", "save ~/hel.txt", "save | gpg -e -o ~/hel.gpg"],
+    },
+    CommandHelp {
+        name: "dump",
+        summary: "print all entries as a script",
+        syntax: &["dump"],
+        args: &[],
+        examples: &["dump"],
+    },
+    CommandHelp {
+        name: "set",
+        summary: "change a session default, when typed at the prompt it is also saved to the init file from the config",
+        syntax: &["set KEY VALUE"],
+        args: &[
+            SETTING_ARG,
+            (
+                "VALUE",
+                "mode: MODE for add NAME and gen NAME, seq: SEQ for them, gen: COUNT for gen, sort: name or date for ls, date: iso or age in listings, output: text or json, clip: clipboard or primary selection for pb, clear: seconds until pb clears the clipboard, 0 keeps it, delay: seconds type waits before typing",
+            ),
+        ],
+        examples: &["set mode N", "set gen 5", "set sort date", "set date age", "set output json", "set clear 10"],
+    },
+    CommandHelp {
+        name: "get",
+        summary: "print the value of a setting, or all settings",
+        syntax: &["get", "get KEY", "settings"],
+        args: &[SETTING_ARG],
+        examples: &["get mode", "settings"],
+    },
+    CommandHelp {
+        name: "config",
+        summary: "show the effective configuration and where each value comes from",
+        syntax: &["config"],
+        args: &[],
+        examples: &["config"],
+    },
+    CommandHelp {
+        name: "help",
+        summary: "list commands or show the usage of one command",
+        syntax: &["help", "help COMMAND"],
+        args: &[("COMMAND", "name of a command")],
+        examples: &["help", "help add"],
+    },
+    CommandHelp {
+        name: "quit",
+        summary: "leave hel, with --batch or a command line the exit status is the one of the last failed command",
+        syntax: &["quit"],
+        args: &[],
+        examples: &["quit"],
+    },
+];
+
+pub fn command_help(name: &str) -> Option<&'static CommandHelp> {
+    COMMANDS_HELP.iter().find(|h| h.name == name)
+}
+
+/// Where and why a command line stops parsing, the expected set holds the
+/// field names of the grammar (NAME, MODE, DATE, ...) or literal tokens.
+#[derive(PartialEq, Debug)]
+pub struct PartialParse {
+    pub offset: usize,
+    pub expected: Vec<&'static str>,
+}
+
+pub fn partial_parse(line: &str) -> Option<PartialParse> {
+    match command_parser::cmd(line) {
+        Ok(_) => None,
+        Err(e) => {
+            let mut expected = e.expected.tokens().collect::<Vec<&'static str>>();
+            expected.sort();
+            Some(PartialParse {
+                offset: e.location.offset,
+                expected,
+            })
+        }
+    }
+}
+
+/// Render a parse error of `input` for the user: the position, what was expected in words, the
+/// offending line with a caret under the error and a suggestion for a misspelled command.
+pub fn parse_error_message(input: &str, err: &peg::error::ParseError<peg::str::LineCol>) -> Vec<String> {
+    let mut tokens = err.expected.tokens().collect::<Vec<&'static str>>();
+    tokens.sort();
+    let is_command = |t: &str| {
+        let t = t.trim_matches('"');
+        t == "settings" || command_help(t).is_some()
+    };
+    let command_expected = tokens.iter().any(|t| is_command(t));
+    let expected = if command_expected {
+        vec!["a command".to_string()]
+    } else {
+        tokens.iter().map(|t| expected_in_words(t)).collect()
+    };
+    let expected = match expected.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+        None => "nothing".to_string(),
+    };
+    let location = &err.location;
+    let line = input.lines().nth(location.line - 1).unwrap_or("");
+    let line_start = input[..location.offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let column = input[line_start..location.offset].chars().count();
+    let mut lines = vec![
+        format!("error at {}:{}: expected {}", location.line, location.column, expected),
+        format!("  {}", line),
+        format!("  {}^", " ".repeat(column)),
+    ];
+    let word = input[location.offset..].split(|c: char| c.is_whitespace() || c == ';').next().unwrap_or("");
+    if command_expected && !word.is_empty() {
+        let commands = COMMANDS_HELP.iter().map(|h| h.name).chain(["settings"]);
+        if let Some(command) = did_you_mean(word, commands) {
+            lines.push(format!("did you mean {}?", command));
+        }
+    }
+    lines
+}
+
+fn expected_in_words(token: &str) -> String {
+    match token {
+        "NAME" => "a name",
+        "MODE" => "a mode like R, N, C, H, B, D or UR, UN, UH, UB",
+        "DATE" => "a date as YYYY-MM-DD or now",
+        "NUMBER" => "a number",
+        "LEN" => "a length",
+        "SEQ" => "a sequence number",
+        "SPACE" => "a space",
+        "TEXT" => "some text",
+        "COMMAND" => "a shell command",
+        "FILE" => "a file name",
+        "FILTER" => "a filter",
+        "KEY" => "a setting like mode, seq, gen, sort, date or output",
+        "EOF" => "the end of the line",
+        t => return t.trim_matches('"').to_string(),
+    }
+    .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_error_message_test() {
+        let message = |line: &str| parse_error_message(line, &command_parser::cmd(line).unwrap_err());
+        assert_eq!(message("ad foo"), vec!["error at 1:1: expected a command", "  ad foo", "  ^", "did you mean add?"]);
+        assert_eq!(
+            message("ls; shw x"),
+            vec!["error at 1:5: expected a command", "  ls; shw x", "      ^", "did you mean show?"]
+        );
+        assert_eq!(message("xyzzy"), vec!["error at 1:1: expected a command", "  xyzzy", "  ^"]);
+        assert_eq!(
+            message("edit x mode=Q"),
+            vec![
+                "error at 1:13: expected a mode like R, N, C, H, B, D or UR, UN, UH, UB",
+                "  edit x mode=Q",
+                "              ^"
+            ]
+        );
+        assert_eq!(
+            message("édit x seq="),
+            vec!["error at 1:1: expected a command", "  édit x seq=", "  ^", "did you mean edit?"]
+        );
+        let script = "ls\nadd x R 99 2022-1-1\ngen x Z";
+        assert_eq!(
+            parse_error_message(script, &command_parser::script(script).unwrap_err())[..3],
+            ["error at 3:8: expected a space", "  gen x Z", "         ^"]
+        );
+    }
+
     #[test]
     fn parse_script_test() {
         assert_eq!(
@@ -122,6 +640,7 @@ add t3 C 99 2022-12-14"###
             Ok(vec![
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t1".to_string(),
                     length: None,
@@ -132,6 +651,7 @@ add t3 C 99 2022-12-14"###
                 })),
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t2".to_string(),
                     length: None,
@@ -142,6 +662,7 @@ add t3 C 99 2022-12-14"###
                 })),
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t3".to_string(),
                     length: None,
@@ -162,6 +683,7 @@ add t3 C 99 2022-12-14
             Ok(vec![
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t1".to_string(),
                     length: None,
@@ -172,6 +694,7 @@ add t3 C 99 2022-12-14
                 })),
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t2".to_string(),
                     length: None,
@@ -182,6 +705,7 @@ add t3 C 99 2022-12-14
                 })),
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t3".to_string(),
                     length: None,
@@ -204,6 +728,7 @@ add t3 C 99 2022-12-14
             Ok(vec![
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t1".to_string(),
                     length: None,
@@ -214,6 +739,7 @@ add t3 C 99 2022-12-14
                 })),
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t2".to_string(),
                     length: None,
@@ -224,6 +750,7 @@ add t3 C 99 2022-12-14
                 })),
                 Command::Add(Password::from_password(Password {
                     parent: None,
+                    alias: None,
                     prefix: None,
                     name: "t3".to_string(),
                     length: None,
@@ -245,6 +772,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: None,
                 mode: Mode::Regular,
                 length: None,
@@ -258,6 +786,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: None,
                 mode: Mode::RegularUpcase,
                 length: None,
@@ -271,6 +800,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: None,
                 mode: Mode::RegularUpcase,
                 length: None,
@@ -284,6 +814,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: Some("#W9".to_string()),
                 mode: Mode::Regular,
                 length: None,
@@ -297,6 +828,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: Some("#W9".to_string()),
                 mode: Mode::NoSpace,
                 length: None,
@@ -310,6 +842,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: Some("#W9".to_string()),
                 mode: Mode::NoSpaceUpcase,
                 length: None,
@@ -323,6 +856,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: Some("#W9".to_string()),
                 mode: Mode::Regular,
                 length: Some(20),
@@ -336,6 +870,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: Some("#W9".to_string()),
                 mode: Mode::RegularUpcase,
                 length: Some(20),
@@ -349,6 +884,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: Some("#W9".to_string()),
                 mode: Mode::HexUpcase,
                 length: Some(20),
@@ -362,6 +898,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: Some("#W9".to_string()),
                 mode: Mode::Base64Upcase,
                 length: Some(20),
@@ -375,6 +912,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: Some("#W9".to_string()),
                 mode: Mode::Decimal,
                 length: Some(20),
@@ -388,6 +926,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: None,
                 mode: Mode::Decimal,
                 length: Some(20),
@@ -401,6 +940,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: None,
                 mode: Mode::NoSpaceCamel,
                 length: Some(20),
@@ -414,6 +954,7 @@ add t3 C 99 2022-12-14
             Ok(Password {
                 name: "ableton89".to_string(),
                 parent: None,
+                alias: None,
                 prefix: None,
                 mode: Mode::Decimal,
                 length: Some(20),
@@ -423,4 +964,164 @@ add t3 C 99 2022-12-14
             })
         );
     }
+
+    #[test]
+    fn parse_unicode_name_test() {
+        assert_eq!(
+            command_parser::name("\"my bank\" R 99 2022-12-14 Konto für Grüße"),
+            Ok(Password {
+                name: "my bank".to_string(),
+                parent: None,
+                alias: None,
+                prefix: None,
+                mode: Mode::Regular,
+                length: None,
+                seq: 99,
+                date: Date::new(2022, 12, 14),
+                comment: Some("Konto für Grüße".to_string())
+            })
+        );
+        assert_eq!(
+            command_parser::name("\"#W 9\" ключ 20R 98 2022-12-14"),
+            Ok(Password {
+                name: "ключ".to_string(),
+                parent: None,
+                alias: None,
+                prefix: Some("#W 9".to_string()),
+                mode: Mode::Regular,
+                length: Some(20),
+                seq: 98,
+                date: Date::new(2022, 12, 14),
+                comment: None
+            })
+        );
+        assert_eq!(command_parser::cmd("enc \"say \\\"hi\\\"\""), Ok(Command::Enc("say \"hi\"".to_string())));
+        assert_eq!(command_parser::cmd("rm cafe\u{301}"), Ok(Command::Rm("caf\u{e9}".to_string())));
+        assert_eq!(command_parser::cmd("mv \"a b\" \"c d\""), Ok(Command::Mv("a b".to_string(), "c d".to_string())));
+        assert_eq!(command_parser::cmd("# заметка"), Ok(Command::Noop));
+        let cmd = command_parser::cmd("add \"my bank\" R 99 2022-12-14 ^\"my folder\"").unwrap();
+        assert_eq!(command_parser::cmd(&cmd.to_string()), Ok(cmd));
+    }
+    #[test]
+    fn help_examples_test() {
+        for help in COMMANDS_HELP {
+            for example in help.examples {
+                match command_parser::cmd(example) {
+                    Ok(Command::Error(_)) | Err(_) => panic!("example {:?} of {} does not parse", example, help.name),
+                    Ok(cmd) => assert!(cmd.to_string().starts_with(help.name) || help.name == "save"),
+                }
+            }
+        }
+        assert_eq!(command_parser::cmd("help"), Ok(Command::Help(None)));
+        assert_eq!(command_parser::cmd("help add"), Ok(Command::Help(Some("add".to_string()))));
+    }
+    #[test]
+    fn parse_chain_test() {
+        let enc = |name: &str| Box::new(Command::Enc(name.to_string()));
+        assert_eq!(
+            command_parser::cmd("enc a; enc b"),
+            Ok(Command::Chain(vec![Command::Enc("a".to_string()), Command::Enc("b".to_string())]))
+        );
+        assert_eq!(command_parser::cmd("enc a;"), Ok(Command::Enc("a".to_string())));
+        assert_eq!(
+            command_parser::cmd("enc foo | qrencode -t utf8"),
+            Ok(Command::Pipe(enc("foo"), "qrencode -t utf8".to_string()))
+        );
+        assert_eq!(
+            command_parser::cmd("enc foo | tr a b | wc -c >> \"out file\""),
+            Ok(Command::Redirect(
+                Box::new(Command::Pipe(Box::new(Command::Pipe(enc("foo"), "tr a b".to_string())), "wc -c".to_string())),
+                "out file".to_string(),
+                true
+            ))
+        );
+        assert_eq!(
+            command_parser::cmd("ls a|b | wc -l; dump > ~/dump.txt"),
+            Ok(Command::Chain(vec![
+                Command::Pipe(Box::new(Command::Ls("a|b".to_string())), "wc -l".to_string()),
+                Command::Redirect(Box::new(Command::Dump(Some("-".to_string()))), "~/dump.txt".to_string(), false),
+            ]))
+        );
+        assert_eq!(
+            command_parser::cmd("comment foo \"a; b\""),
+            Ok(Command::Comment("foo".to_string(), Some("a; b".to_string())))
+        );
+        assert_eq!(command_parser::cmd("source gpg -d x.gpg |"), Ok(Command::Source("gpg -d x.gpg |".to_string())));
+        assert_eq!(
+            command_parser::cmd("save |gpg -e -r me; ls"),
+            Ok(Command::Dump(Some("|gpg -e -r me; ls".to_string())))
+        );
+        assert_eq!(
+            command_parser::cmd("pass / sec;ret | x"),
+            Ok(Command::Pass("/".to_string(), Some("sec;ret | x".to_string())))
+        );
+        assert!(command_parser::cmd("| wc").is_err());
+        for line in ["enc a; enc b", "enc foo | tr a b > out", "comment foo \\\"a; b\""] {
+            assert_eq!(command_parser::cmd(line).unwrap().to_string(), line);
+        }
+        // scripts keep one plain command per line
+        assert_eq!(
+            command_parser::script("comment foo backup > old.txt\nenc a;b\ncomment x a | b; c"),
+            Ok(vec![
+                Command::Comment("foo".to_string(), Some("backup > old.txt".to_string())),
+                Command::Enc("a;b".to_string()),
+                Command::Comment("x".to_string(), Some("a | b; c".to_string())),
+            ])
+        );
+        // comments from before the quoting keep their quotes, quoted ones round-trip
+        let old = "add foo R 99 2022-12-30 \"work\" account";
+        let add = command_parser::script(old).unwrap().remove(0);
+        assert!(matches!(&add, Command::Add(p) if p.lock().borrow().comment.as_deref() == Some("\"work\" account")));
+        assert_eq!(add.to_string(), "add foo R 99 2022-12-30 \\\"\\\"work\\\" account\"");
+        for comment in ["\"work\" account", "a; b", "\\\"x\"", "| y"] {
+            let line = Command::Comment("foo".to_string(), Some(comment.to_string())).to_string();
+            assert_eq!(
+                command_parser::script(&line),
+                Ok(vec![Command::Comment("foo".to_string(), Some(comment.to_string()))])
+            );
+            assert_eq!(command_parser::cmd(&line), Ok(Command::Comment("foo".to_string(), Some(comment.to_string()))));
+        }
+    }
+    #[test]
+    fn partial_parse_test() {
+        assert_eq!(partial_parse("enc foo"), None);
+        assert_eq!(partial_parse("enc ").unwrap().expected, vec!["NAME"]);
+        assert_eq!(partial_parse("add name 20").unwrap().offset, 11);
+        assert_eq!(partial_parse("add name 20").unwrap().expected, vec!["MODE", "SPACE"]);
+        assert_eq!(partial_parse("add name R 99 ").unwrap().expected, vec!["DATE"]);
+        assert_eq!(partial_parse("enc foo bar").unwrap().offset, 8);
+        assert_eq!(partial_parse("enc foo | ").unwrap().expected, vec!["COMMAND"]);
+        assert_eq!(partial_parse("enc foo > ").unwrap().expected, vec!["FILE"]);
+    }
+    #[test]
+    fn parse_edit_test() {
+        assert_eq!(
+            command_parser::cmd("edit -y \"my bank\" mode=UN len= seq=98 date=2022-12-14 comment=\"a b\" prefix="),
+            Ok(Command::Edit(
+                "my bank".to_string(),
+                vec![
+                    EditField::Mode(Mode::NoSpaceUpcase),
+                    EditField::Length(None),
+                    EditField::Seq(98),
+                    EditField::Date(Date::new(2022, 12, 14)),
+                    EditField::Comment(Some("a b".to_string())),
+                    EditField::Prefix(None),
+                ],
+                true
+            ))
+        );
+        assert_eq!(
+            command_parser::cmd("edit t1 len=12"),
+            Ok(Command::Edit("t1".to_string(), vec![EditField::Length(Some(12))], false))
+        );
+        assert!(command_parser::cmd("edit t1").is_err());
+        assert_eq!(
+            command_parser::cmd("rename ableton89 \"Ableton Live\""),
+            Ok(Command::Rename("ableton89".to_string(), Some("Ableton Live".to_string())))
+        );
+        assert_eq!(command_parser::cmd("rename ableton89"), Ok(Command::Rename("ableton89".to_string(), None)));
+        assert!(command_parser::cmd("edit t1 mode=X").is_err());
+        let cmd = command_parser::cmd("edit t1 comment=\"a b\" len=").unwrap();
+        assert_eq!(command_parser::cmd(&cmd.to_string()), Ok(cmd));
+    }
 }
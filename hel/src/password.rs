@@ -1,6 +1,7 @@
 use crate::skey::SKey;
-use crate::structs::Mode;
+use crate::structs::{EditField, Mode};
 use crate::utils::date::Date;
+use crate::utils::{normalize_name, quote_name, quote_text};
 use parking_lot::ReentrantMutex;
 use std::cell::RefCell;
 use std::sync::Arc;
@@ -8,6 +9,7 @@ use std::sync::Arc;
 pub type Name = String;
 pub type Prefix = Option<String>;
 pub type Comment = Option<String>;
+pub type Alias = Option<String>;
 pub type PasswordRef = Arc<ReentrantMutex<RefCell<Password>>>;
 pub type Parent = Option<PasswordRef>;
 pub type Length = Option<u32>;
@@ -23,6 +25,7 @@ pub struct Password {
     pub seq: Seq,
     pub date: Date,
     pub comment: Comment,
+    pub alias: Alias,
 }
 
 impl Password {
@@ -43,6 +46,7 @@ impl Password {
             date,
             comment,
             parent: None,
+            alias: None,
             seq,
         }
     }
@@ -57,6 +61,7 @@ impl Password {
             seq: password.seq,
             date: password.date.clone(),
             comment: password.comment.clone(),
+            alias: password.alias.clone(),
         })))
     }
 
@@ -64,8 +69,19 @@ impl Password {
         Arc::new(ReentrantMutex::new(RefCell::new(password)))
     }
 
+    pub fn edit(&mut self, field: &EditField) {
+        match field {
+            EditField::Prefix(p) => self.prefix = p.clone(),
+            EditField::Length(l) => self.length = *l,
+            EditField::Mode(m) => self.mode = m.clone(),
+            EditField::Seq(s) => self.seq = *s,
+            EditField::Date(d) => self.date = *d,
+            EditField::Comment(c) => self.comment = c.clone(),
+        }
+    }
+
     pub fn encode(&self, secret: &str) -> String {
-        let skey = SKey::new(&self.name, self.seq, secret);
+        let skey = SKey::new(&normalize_name(&self.name), self.seq, secret);
         let (sep, len) = match (&self.length, &self.mode) {
             (Some(n), Mode::NoSpace | Mode::NoSpaceUpcase) => ("", n),
             (Some(n), Mode::Base64 | Mode::Base64Upcase | Mode::Hex | Mode::HexUpcase) => ("", n),
@@ -102,8 +118,15 @@ impl Password {
 
 impl std::string::ToString for Password {
     fn to_string(&self) -> String {
+        self.to_string_with_date(&self.date.to_string())
+    }
+}
+
+impl Password {
+    /// Same as `to_string`, but with the date rendered by the caller.
+    pub fn to_string_with_date(&self, date: &str) -> String {
         let prefix = match self.prefix.as_ref() {
-            Some(s) => format!("{} ", s),
+            Some(s) => format!("{} ", quote_name(s)),
             None => "".to_string(),
         };
         let length = match self.length {
@@ -111,14 +134,15 @@ impl std::string::ToString for Password {
             None => "".to_string(),
         };
         let comment = match self.comment.as_ref() {
-            Some(s) => format!(" {}", s),
+            Some(s) => format!(" {}", quote_text(s)),
             None => "".to_string(),
         };
         let parent = match &self.parent {
-            Some(s) => format!(" ^{}", s.lock().borrow().name),
+            Some(s) => format!(" ^{}", quote_name(&s.lock().borrow().name)),
             None => "".to_string(),
         };
-        format!("{:>6}{} {}{} {} {}{}{}", prefix, self.name, length, self.mode, self.seq, self.date, comment, parent)
+        let name = quote_name(&self.name);
+        format!("{:>6}{} {}{} {} {}{}{}", prefix, name, length, self.mode, self.seq, date, comment, parent)
     }
 }
 
@@ -301,5 +325,9 @@ mod tests {
         assert_eq!(pwd.encode(sec), "#Q3a168468");
         pwd.mode = Mode::NoSpaceCamel;
         assert_eq!(pwd.encode(sec), "#Q3aRossBe");
+
+        let composed = Password::new(None, "caf\u{e9}".to_string(), None, Mode::Regular, 99, dat, None);
+        let decomposed = Password::new(None, "cafe\u{301}".to_string(), None, Mode::Regular, 99, dat, None);
+        assert_eq!(composed.encode(sec), decomposed.encode(sec));
     }
 }
@@ -0,0 +1,258 @@
+extern crate peg;
+
+use crate::password::Password;
+use crate::utils::date::Date;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Ordering;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Field {
+    Name,
+    Alias,
+    Prefix,
+    Comment,
+    Parent,
+    Mode,
+    Seq,
+    Len,
+    Date,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Op {
+    Is,
+    Matches,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug)]
+pub enum Value {
+    Text(String),
+    Re(Regex),
+    Num(u32),
+    Date(Date),
+}
+
+#[derive(Debug)]
+pub enum Query {
+    Re(Regex),
+    Folder(String),
+    Field(Field, Op, Value),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+peg::parser! {
+    grammar query_parser() for str {
+        pub rule query() -> Query = _? q:or() _? { q }
+
+        rule _() = quiet!{[' ' | '\t']+}
+        rule keyword() = ("and" / "or" / "not") ![^ ' ' | '\t' | '(' | ')']
+        rule qchar() -> char = "\\" c:['"' | '\\'] { c } / c:[^'"' | '\n'] { c }
+        rule word() -> String = "\"" s:qchar()* "\"" { s.into_iter().collect() }
+            / !keyword() v:$([^ ' ' | '\t' | '(' | ')' | '"']+) { v.to_string() }
+        rule value() -> String = v:quiet!{word()} { v } / expected!("value")
+
+        rule or() -> Query = l:and() r:(_ "or" _ r:and() { r })* {
+            r.into_iter().fold(l, |l, r| Query::Or(Box::new(l), Box::new(r)))
+        }
+        rule and() -> Query = l:not() r:(_ ("and" _)? r:not() { r })* {
+            r.into_iter().fold(l, |l, r| Query::And(Box::new(l), Box::new(r)))
+        }
+        rule not() -> Query = "not" _ q:not() { Query::Not(Box::new(q)) }
+            / "(" _? q:or() _? ")" { q }
+            / term()
+
+        rule field() -> Field = f:$(['a'..='z']+) {?
+            match f {
+                "name" => Ok(Field::Name),
+                "alias" => Ok(Field::Alias),
+                "prefix" => Ok(Field::Prefix),
+                "comment" => Ok(Field::Comment),
+                "parent" => Ok(Field::Parent),
+                "mode" => Ok(Field::Mode),
+                "seq" => Ok(Field::Seq),
+                "len" => Ok(Field::Len),
+                "date" => Ok(Field::Date),
+                _ => Err("field"),
+            }
+        }
+        rule op() -> Op = ":~" { Op::Matches } / ":" { Op::Is } / "=" { Op::Is }
+            / "<=" { Op::Le } / ">=" { Op::Ge } / "<" { Op::Lt } / ">" { Op::Gt }
+        rule term() -> Query = f:field() o:op() v:value() {? Query::field(f, o, &v) }
+            / "^" v:value() { Query::Folder(v) }
+            / !(field() op()) v:value() {? Regex::new(&v).map(Query::Re).or(Err("regular expression")) }
+    }
+}
+
+impl Query {
+    /// Parse an ls filter. A single word the query grammar rejects, like `(a|b)c`, is still taken
+    /// as one regular expression.
+    pub fn parse(filter: &str) -> Result<Self, String> {
+        lazy_static! {
+            static ref FIELD_RE: Regex =
+                Regex::new(r"(^|[\s(])(name|alias|prefix|comment|parent|mode|seq|len|date)(:|<|>|=)").unwrap();
+        }
+        match query_parser::query(filter) {
+            Ok(q) => Ok(q),
+            Err(e) if FIELD_RE.is_match(filter) || filter.trim().contains(char::is_whitespace) => {
+                Err(format!("failed to parse query: {}", e))
+            }
+            Err(_) => Regex::new(filter).map(Query::Re).map_err(|e| format!("failed to parse re: {:?}", e)),
+        }
+    }
+
+    fn field(field: Field, op: Op, value: &str) -> Result<Self, &'static str> {
+        let value = match (field, op) {
+            (_, Op::Matches) => match RegexBuilder::new(value).case_insensitive(true).build() {
+                Ok(re) => Value::Re(re),
+                Err(_) => return Err("regular expression"),
+            },
+            (Field::Seq | Field::Len, _) => Value::Num(value.parse().or(Err("number"))?),
+            (Field::Date, _) => Value::Date(parse_date(value).ok_or("date as YYYY-MM-DD")?),
+            (_, Op::Is) => Value::Text(value.to_string()),
+            _ => return Err(": or :~ for a text field"),
+        };
+        Ok(Query::Field(field, op, value))
+    }
+
+    pub fn matches(&self, pwd: &Password) -> bool {
+        match self {
+            Query::Re(re) => {
+                re.is_match(&pwd.to_string())
+                    || re.is_match(&pwd.name)
+                    || pwd.alias.as_ref().is_some_and(|a| re.is_match(a))
+                    || pwd.comment.as_ref().is_some_and(|c| re.is_match(c))
+            }
+            Query::Folder(folder) => in_folder(pwd, folder),
+            Query::Field(field, op, value) => field_matches(pwd, *field, *op, value),
+            Query::Not(q) => !q.matches(pwd),
+            Query::And(a, b) => a.matches(pwd) && b.matches(pwd),
+            Query::Or(a, b) => a.matches(pwd) || b.matches(pwd),
+        }
+    }
+}
+
+fn parse_date(value: &str) -> Option<Date> {
+    if value == "now" {
+        return Some(Date::now());
+    }
+    let parts = value.splitn(3, '-').map(|p| p.parse::<u32>().ok()).collect::<Option<Vec<u32>>>()?;
+    match parts[..] {
+        [y, m, d] => Date::try_new(y as i32, m, d).ok(),
+        _ => None,
+    }
+}
+
+/// Whether `folder` is the parent of `pwd` or one of its ancestors.
+fn in_folder(pwd: &Password, folder: &str) -> bool {
+    let mut parent = pwd.parent.clone();
+    while let Some(p) = parent {
+        let p = p.lock();
+        let p = p.borrow();
+        if p.name.to_lowercase() == folder.to_lowercase() {
+            return true;
+        }
+        parent = p.parent.clone();
+    }
+    false
+}
+
+fn field_matches(pwd: &Password, field: Field, op: Op, value: &Value) -> bool {
+    let ordering = match (field, value) {
+        (Field::Seq, Value::Num(n)) => Some(pwd.seq.cmp(n)),
+        (Field::Len, Value::Num(n)) => pwd.length.map(|l| l.cmp(n)),
+        (Field::Date, Value::Date(d)) => Some(pwd.date.cmp(d)),
+        _ => None,
+    };
+    if let Some(ordering) = ordering {
+        return match op {
+            Op::Is => ordering == Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+            Op::Matches => false,
+        };
+    }
+    let text = match field {
+        Field::Name => Some(pwd.name.to_string()),
+        Field::Alias => pwd.alias.clone(),
+        Field::Prefix => pwd.prefix.clone(),
+        Field::Comment => pwd.comment.clone(),
+        Field::Parent => Some(match &pwd.parent {
+            Some(p) => p.lock().borrow().name.to_string(),
+            None => "/".to_string(),
+        }),
+        Field::Mode => Some(pwd.mode.to_string()),
+        Field::Seq => Some(pwd.seq.to_string()),
+        Field::Len => pwd.length.map(|l| l.to_string()),
+        Field::Date => Some(pwd.date.to_string()),
+    };
+    match (text, value) {
+        (Some(t), Value::Re(re)) => re.is_match(&t),
+        (Some(t), Value::Text(v)) => t.to_lowercase() == v.to_lowercase(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::password::PasswordRef;
+    use crate::structs::Mode;
+
+    #[test]
+    fn query_test() {
+        let pwd = Password::new(
+            Some("#W9".to_string()),
+            "my bank".to_string(),
+            Some(12),
+            Mode::Hex,
+            42,
+            Date::new(2021, 6, 1),
+            Some("me@gmail.com".to_string()),
+        );
+        let matches = |filter: &str| Query::parse(filter).unwrap().matches(&pwd);
+        assert!(matches("mode:H"));
+        assert!(matches("mode:h seq<50 len>=12"));
+        assert!(!matches("mode:UH"));
+        assert!(matches("date<2022-01-01 and comment:~GMAIL"));
+        assert!(matches("parent:/ and not (seq>=50 or len<12)"));
+        assert!(matches("name:\"My Bank\""));
+        assert!(matches("seq:99 or bank"));
+        assert!(!matches("seq:99 or nope"));
+        assert!(matches("ba.k"));
+        assert!(matches("nope or gmail"));
+        assert!(!matches("bank and not gmail"));
+        assert!(matches("(b|m)a?nk"));
+        assert!(Query::parse("seq<abc").unwrap_err().starts_with("failed to parse query"));
+        assert!(Query::parse("bank or (gmail").unwrap_err().starts_with("failed to parse query"));
+        assert!(Query::parse("(foo").unwrap_err().starts_with("failed to parse re"));
+        assert!(Query::parse("mode<H").is_err());
+    }
+
+    #[test]
+    fn query_folder_test() {
+        let entry = |name: &str, parent: Option<&PasswordRef>| {
+            let pwd = Password::new(None, name.to_string(), None, Mode::Regular, 99, Date::new(2022, 1, 1), None);
+            let pwd = Password::from_password(pwd);
+            pwd.lock().borrow_mut().parent = parent.cloned();
+            pwd
+        };
+        let bank = entry("Bank", None);
+        let cards = entry("cards", Some(&bank));
+        let visa = entry("visa", Some(&cards));
+        let matches = |filter: &str, pwd: &PasswordRef| Query::parse(filter).unwrap().matches(&pwd.lock().borrow());
+        assert!(matches("^bank", &cards));
+        assert!(matches("^bank", &visa));
+        assert!(matches("^cards and visa", &visa));
+        assert!(!matches("^bank", &bank));
+        assert!(!matches("^visa", &cards));
+        assert!(!matches("^ba", &visa));
+    }
+}
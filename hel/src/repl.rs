@@ -1,7 +1,8 @@
-use crate::lk::LKRef;
+use crate::lk::{LKRef, LK};
 use crate::parser::command_parser;
-use crate::structs::{Command, LKErr, LKOut, HISTORY_FILE};
+use crate::structs::{Command, LKErr, LKOut};
 use crate::utils::editor::{password, Editor, EditorRef};
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct LKRead {
@@ -14,11 +15,13 @@ pub struct LKRead {
 }
 
 #[derive(Debug)]
-pub struct LKEval<'a> {
+pub struct LKEval {
     pub rl: EditorRef,
-    pub cmd: Command<'a>,
+    pub cmd: Command,
     pub state: LKRef,
     pub read_password: fn(String) -> std::io::Result<String>,
+    /// The command was typed at the prompt, not read from a script, stdin or the command line.
+    pub interactive: bool,
 }
 
 #[derive(Debug)]
@@ -26,6 +29,7 @@ pub struct LKPrint {
     pub out: LKOut,
     pub quit: bool,
     pub state: LKRef,
+    pub error: Option<LKErr>,
 }
 
 impl LKRead {
@@ -41,10 +45,11 @@ impl LKRead {
     }
 
     pub fn read(&mut self) -> LKEval {
-        let history_file = HISTORY_FILE.to_str().unwrap();
+        let history_file = self.state.lock().borrow().config.path("history");
+        let history_file = history_file.to_str().unwrap();
         self.cmd = match &self.input {
             Some(cmd) => cmd.to_string(),
-            None => match self.rl.lock().readline(&*self.prompt) {
+            None => match self.rl.lock().readline(&self.current_prompt()) {
                 Ok(str) => str,
                 Err(LKErr::EOF) => "quit".to_string(),
                 Err(LKErr::Error(_)) => "quit".to_string(),
@@ -59,12 +64,29 @@ impl LKRead {
             }
         };
         match command_parser::cmd(&self.cmd) {
-            Ok(cmd) => LKEval::new(self.rl.clone(), cmd, self.state.clone(), self.read_password),
+            Ok(cmd) => {
+                let mut eval = LKEval::new(self.rl.clone(), cmd, self.state.clone(), self.read_password);
+                eval.interactive = self.input.is_none();
+                eval
+            }
             Err(err) => {
                 self.rl.lock().add_history_entry(&self.cmd);
                 self.rl.lock().save_history(&history_file).ok();
-                LKEval::new(self.rl.clone(), Command::Error(LKErr::ParseError(err)), self.state.clone(), self.read_password)
-            },
+                LKEval::new(
+                    self.rl.clone(),
+                    Command::Error(LKErr::ParseError(self.cmd.to_string(), err)),
+                    self.state.clone(),
+                    self.read_password,
+                )
+            }
+        }
+    }
+
+    /// The prompt with the current folder in front of it, unless we are at the top.
+    pub fn current_prompt(&self) -> String {
+        match &self.state.lock().borrow().cwd {
+            Some(_) => format!("{}{}", LK::path(&self.state.lock().borrow().cwd), self.prompt),
+            None => self.prompt.to_string(),
         }
     }
 
@@ -73,104 +95,192 @@ impl LKRead {
     pub fn quit(&mut self) {}
 }
 
-impl<'a> LKEval<'a> {
-    pub fn new(rl: EditorRef, cmd: Command<'a>, state: LKRef, read_password: fn(String) -> std::io::Result<String>) -> Self {
+impl LKEval {
+    pub fn new(
+        rl: EditorRef,
+        cmd: Command,
+        state: LKRef,
+        read_password: fn(String) -> std::io::Result<String>,
+    ) -> Self {
         Self {
             rl,
             cmd,
             state,
             read_password,
+            interactive: false,
         }
     }
 
-    pub fn news(cmd: Command<'a>, state: LKRef) -> Self {
+    pub fn news(cmd: Command, state: LKRef) -> Self {
         LKEval::new(Editor::new(), cmd, state, |_| { Err(std::io::Error::new(std::io::ErrorKind::NotConnected, "could not read password")) })
     }
 
-    pub fn newd(cmd: Command<'a>, state: LKRef, read_password: fn(String) -> std::io::Result<String>) -> Self {
+    pub fn newd(cmd: Command, state: LKRef, read_password: fn(String) -> std::io::Result<String>) -> Self {
         LKEval::new(Editor::new(), cmd, state, read_password)
     }
 
     pub fn eval(&self) -> LKPrint {
         let out = LKOut::new();
-        let mut quit: bool = false;
-        let history_file = HISTORY_FILE.to_str().unwrap();
-        let mut to_history = true;
+        let history_file = self.state.lock().borrow().config.path("history");
+        let history_file = history_file.to_str().unwrap();
 
         self.rl.lock().clear_history();
-        self.rl.lock().load_history(&history_file).ok();
+        self.rl.lock().load_history(history_file).ok();
+
+        let (quit, to_history) = self.eval_cmd(&out, &self.cmd);
+
+        if to_history {
+            self.rl.lock().add_history_entry(self.cmd.to_string().as_str());
+            self.rl.lock().save_history(history_file).ok();
+        }
+
+        LKPrint::new(out, quit, self.state.clone())
+    }
+
+    /// Evaluate one command into `out`, returns whether to quit and whether the line belongs to the history.
+    pub fn eval_cmd(&self, out: &LKOut, cmd: &Command) -> (bool, bool) {
+        let mut quit: bool = false;
+        let mut to_history = true;
 
-        match &self.cmd {
+        match cmd {
             Command::Quit => {
                 out.e("Bye!".to_string());
                 quit = true;
             }
+            Command::Ls(filter) if self.state.lock().borrow().settings.sort == "date" => {
+                self.cmd_ls(out, filter.to_string(), |a, b| a.lock().borrow().date.cmp(&b.lock().borrow().date))
+            }
             Command::Ls(filter) => {
-                self.cmd_ls(&out, filter.to_string(), |a, b| a.lock().borrow().name.cmp(&b.lock().borrow().name))
+                self.cmd_ls(out, filter.to_string(), |a, b| a.lock().borrow().name.cmp(&b.lock().borrow().name))
             }
             Command::Ld(filter) => {
-                self.cmd_ls(&out, filter.to_string(), |a, b| a.lock().borrow().date.cmp(&b.lock().borrow().date))
+                self.cmd_ls(out, filter.to_string(), |a, b| a.lock().borrow().date.cmp(&b.lock().borrow().date))
             }
-            Command::Add(name) => self.cmd_add(&out, &name),
-            Command::Keep(name) => self.cmd_keep(&out, &name),
-            Command::Comment(name, comment) => self.cmd_comment(&out, &name, &comment),
+            Command::Find(query) => self.cmd_find(out, query),
+            Command::Add(name) => self.cmd_add(out, name),
+            Command::AddDefault(name) => self.cmd_add_default(out, name),
+            Command::Keep(name) => self.cmd_keep(out, name),
+            Command::Comment(name, comment) => self.cmd_comment(out, name, comment),
+            Command::Edit(name, fields, yes) => self.cmd_edit(out, name, fields, *yes),
+            Command::Rename(name, alias) => self.cmd_rename(out, name, alias),
+            Command::Show(name) => self.cmd_show(out, name),
+            Command::Tree(folder) => self.cmd_tree(out, folder),
+            Command::Cd(folder) => self.cmd_cd(out, folder),
+            Command::Pwd => self.cmd_pwd(out),
             Command::Rm(name) => match self.get_password(name) {
                 Some(pwd) => {
                     self.state.lock().borrow_mut().db.remove(&pwd.lock().borrow().name);
+                    self.state.lock().borrow_mut().defaults.remove(&pwd.lock().borrow().name);
+                    // leave cwd when it is the removed entry or below it
+                    let mut folder = self.state.lock().borrow().cwd.clone();
+                    while let Some(f) = folder {
+                        if Arc::ptr_eq(&f, &pwd) {
+                            self.state.lock().borrow_mut().cwd = None;
+                            break;
+                        }
+                        folder = f.lock().borrow().parent.clone();
+                    }
                     out.o(format!("removed {}", pwd.lock().borrow().name));
                 }
-                None => out.e(format!("error: password {} not found", name)),
+                None => {
+                    out.fail(LKErr::NotFound(format!("password {} not found", name)));
+                    self.suggest_name(out, name);
+                }
             },
             Command::Enc(name) => {
-                self.cmd_enc(&out, name);
+                self.cmd_enc(out, name);
             }
-            Command::Gen(num, name) => self.cmd_gen(&out, &num, &name),
-            Command::PasteBuffer(command) => self.cmd_pb(&out, command),
+            Command::Gen(num, name) => self.cmd_gen(out, num, name),
+            Command::GenDefault(num, name) => self.cmd_gen_default(out, num, name),
+            Command::PasteBuffer(command) => self.cmd_pb(out, command),
+            Command::Type(name, user) => self.cmd_type(out, name, *user),
             Command::Source(script) => {
-                quit = self.cmd_source(&out, script);
+                quit = self.cmd_source(out, script);
             }
-            Command::Dump(script) => self.cmd_dump(&out, script),
-            Command::Pass(name, None) => self.cmd_pass(&out, &name, &None),
-            Command::Pass(name, pass) => { to_history = false; self.cmd_pass(&out, &name, &pass); },
+            Command::Dump(script) => self.cmd_dump(out, script),
+            Command::Pass(name, None) => self.cmd_pass(out, name, &None),
+            Command::Pass(name, pass) => { to_history = false; self.cmd_pass(out, name, pass); },
             Command::UnPass(name) => match self.state.lock().borrow_mut().secrets.remove(name) {
                 Some(_) => out.o(format!("Removed saved password for {}", name)),
-                None => out.e(format!("error: saved password for {} not found", name)),
+                None => out.fail(LKErr::NotFound(format!("saved password for {} not found", name))),
             },
-            Command::Correct(name) => self.cmd_correct(&out, name, true, None),
-            Command::Uncorrect(name) => self.cmd_correct(&out, name, false, None),
-            Command::Noop => { to_history = false; },
-            Command::Help => {
-                out.o("HELP".to_string());
+            Command::Defaults(name, fields) => self.cmd_defaults(out, name, fields),
+            Command::UnDefaults(name) => self.cmd_undefaults(out, name),
+            Command::Foreach(filter, template, yes) => {
+                quit = self.cmd_foreach(out, filter, template, yes);
             }
-            Command::Mv(name, folder) => self.cmd_mv(&out, &name, &folder),
+            Command::Set(key, value) => self.cmd_set(out, key, value),
+            Command::Get(key) => self.cmd_get(out, key),
+            Command::Config => self.cmd_config(out),
+            Command::Correct(name) => self.cmd_correct(out, name, true, None),
+            Command::Uncorrect(name) => self.cmd_correct(out, name, false, None),
+            Command::Noop => { to_history = false; },
+            Command::Help(topic) => self.cmd_help(out, topic),
+            Command::Mv(name, folder) => self.cmd_mv(out, name, folder),
             Command::Error(error) => {
                 to_history = false;
-                match error {
-                    LKErr::ParseError(e) => out.e(e.to_string()),
-                    LKErr::ReadError(e) => out.e(e.to_string()),
-                    LKErr::EOF => out.e("error: end of file".to_string()),
-                    LKErr::Error(e) => out.e(format!("error: {}", e.to_string())),
-                };
+                out.fail(error.clone());
             },
+            Command::Chain(cmds) => {
+                for cmd in cmds {
+                    let (q, h) = self.eval_cmd(out, cmd);
+                    to_history &= h;
+                    if q {
+                        quit = true;
+                        break;
+                    }
+                }
+            }
+            Command::Pipe(cmd, command) => {
+                let piped = LKOut::new();
+                (quit, to_history) = self.eval_cmd(&piped, cmd);
+                piped.copy_err(out);
+                self.cmd_pipe(out, &self.out_data(&piped), command);
+            }
+            Command::Redirect(cmd, file, append) => {
+                let redirected = LKOut::new();
+                (quit, to_history) = self.eval_cmd(&redirected, cmd);
+                redirected.copy_err(out);
+                self.cmd_redirect(out, &self.out_data(&redirected), file, *append);
+            }
         }
 
-        if to_history {
-            self.rl.lock().add_history_entry(self.cmd.to_string().as_str());
-            self.rl.lock().save_history(&history_file).ok();
-        }
+        (quit, to_history)
+    }
 
-        LKPrint::new(out, quit, self.state.clone())
+    /// What a pipe or a redirect gets from the command, JSON in the JSON output mode.
+    fn out_data(&self, out: &LKOut) -> String {
+        if self.state.lock().borrow().settings.json() {
+            out.json()
+        } else {
+            out.data()
+        }
     }
 }
 
 impl LKPrint {
     pub fn new(out: LKOut, quit: bool, state: LKRef) -> Self {
-        Self { out, quit, state }
+        let error = out.error();
+        Self {
+            out,
+            quit,
+            state,
+            error,
+        }
+    }
+
+    /// The exit status for this result, 0 when the command did not fail.
+    pub fn exit_code(&self) -> i32 {
+        self.error.as_ref().map(|e| e.exit_code()).unwrap_or(0)
     }
 
     pub fn print(&mut self) -> bool {
         self.out.print_err();
-        self.out.print_out();
+        if self.state.lock().borrow().settings.json() {
+            println!("{}", self.out.json());
+        } else {
+            self.out.print_out();
+        }
         return !self.quit;
     }
 }
@@ -186,10 +296,12 @@ mod tests {
     use super::*;
     use std::collections::HashSet;
     use crate::lk::LK;
+    use crate::parser::command_parser;
     use crate::password::Password;
-    use crate::structs::Mode;
+    use crate::structs::{EditField, Mode};
     use crate::utils::date::Date;
     use parking_lot::ReentrantMutex;
+    use regex::Regex;
     use std::cell::RefCell;
     use std::collections::HashMap;
     use std::sync::Arc;
@@ -210,6 +322,7 @@ mod tests {
             date: Date::new(2022, 12, 30),
             comment: Some("comment".to_string()),
             parent: None,
+            alias: None,
         });
         assert_eq!(
             LKEval::news(Command::Add(pwd1.clone()), lk.clone())
@@ -250,6 +363,7 @@ mod tests {
             date: Date::new(2022, 12, 31),
             comment: Some("bli blup".to_string()),
             parent: None,
+            alias: None,
         });
         assert_eq!(
             LKEval::news(Command::Add(pwd2.clone()), lk.clone())
@@ -432,4 +546,423 @@ mod tests {
         LKEval::news(Command::Pass("t1".to_string(), Some("other pw".to_string())), lk.clone()).eval();
         assert_eq!(lk.lock().borrow().secrets[&"t1".to_string()], "other pw");
     }
+    #[test]
+    fn exec_cmd_help() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let out = LKEval::news(Command::Help(None), lk.clone()).eval().out.output();
+        assert_eq!(out.len(), crate::parser::COMMANDS_HELP.len() + 3);
+        assert!(out[0].starts_with("add "));
+        let out = LKEval::news(Command::Help(Some("add".to_string())), lk.clone()).eval().out.output();
+        assert_eq!(out[0], "add - add a new password entry");
+        assert!(out.iter().any(|l| l.contains("YYYY-MM-DD")));
+        assert!(out.iter().any(|l| l.contains("C camel case")));
+        assert_eq!(
+            LKEval::news(Command::Help(Some("nope".to_string())), lk.clone()).eval(),
+            LKPrint::new(
+                LKOut::from_vecs(
+                    vec![],
+                    vec!["error: unknown command nope, type help for the list of commands".to_string()]
+                ),
+                false,
+                lk.clone()
+            )
+        );
+    }
+    #[test]
+    fn exec_cmd_edit() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let t1 = Password::from_password(Password::new(
+            None,
+            "t1".to_string(),
+            None,
+            Mode::Regular,
+            99,
+            Date::new(2022, 12, 30),
+            None,
+        ));
+        LKEval::news(Command::Add(t1.clone()), lk.clone()).eval();
+        lk.lock().borrow_mut().secrets.insert("/".to_string(), "a".to_string());
+        let old = t1.lock().borrow().encode("a");
+        let fields =
+            vec![EditField::Seq(98), EditField::Mode(Mode::NoSpaceCamel), EditField::Comment(Some("x y".to_string()))];
+        let print = LKEval::news(Command::Edit("t1".to_string(), fields, true), lk.clone()).eval();
+        let new = t1.lock().borrow().encode("a");
+        assert_eq!(
+            print.out.data(),
+            [
+                "-       t1 R 99 2022-12-30".to_string(),
+                "+       t1 C 98 2022-12-30 x y".to_string(),
+                format!("password changes from {} to {}", old, new),
+                "changed t1".to_string(),
+            ]
+            .join("\n")
+        );
+        assert_eq!(t1.lock().borrow().to_string(), "      t1 C 98 2022-12-30 x y");
+        assert_eq!(
+            LKEval::news(Command::Edit("t9".to_string(), vec![EditField::Seq(1)], true), lk.clone())
+                .eval()
+                .out
+                .output(),
+            vec!["error: password with name t9 not found".to_string(), "did you mean t1?".to_string()]
+        );
+        // outside of the prompt nothing is asked, printed to stdout or changed
+        let print = LKEval::news(Command::Edit("t1".to_string(), vec![EditField::Seq(1)], false), lk.clone()).eval();
+        assert_eq!(print.out.data(), "");
+        assert_eq!(
+            print.out.output(),
+            vec!["warning: no terminal to confirm on, use -y to apply without asking", "error: t1 not changed"]
+        );
+        assert_eq!(t1.lock().borrow().seq, 98);
+    }
+    #[test]
+    fn exec_cmd_rename() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        for name in ["t1", "t2"] {
+            let pwd = Password::new(None, name.to_string(), None, Mode::Regular, 99, Date::new(2022, 12, 30), None);
+            LKEval::news(Command::Add(Password::from_password(pwd)), lk.clone()).eval();
+        }
+        lk.lock().borrow_mut().secrets.insert("/".to_string(), "a".to_string());
+        let enc = |name: &str| LKEval::news(Command::Enc(name.to_string()), lk.clone()).eval().out.data();
+        let before = enc("t1");
+        LKEval::news(Command::Rename("t1".to_string(), Some("my first".to_string())), lk.clone()).eval();
+        assert_eq!(enc("my first"), before);
+        assert_eq!(
+            LKEval::news(Command::Ls("first".to_string()), lk.clone()).eval().out.data(),
+            "  1       t1 R 99 2022-12-30 [my first]"
+        );
+        assert_eq!(
+            LKEval::news(Command::Dump(Some("-".to_string())), lk.clone()).eval().out.data(),
+            "add       t1 R 99 2022-12-30\nrename t1 \"my first\"\nadd       t2 R 99 2022-12-30"
+        );
+        assert_eq!(
+            LKEval::news(Command::Rename("t2".to_string(), Some("my first".to_string())), lk.clone())
+                .eval()
+                .out
+                .output(),
+            vec!["error: name my first is already used".to_string()]
+        );
+        LKEval::news(Command::Rename("my first".to_string(), None), lk.clone()).eval();
+        assert_eq!(lk.lock().borrow().db["t1"].lock().borrow().alias, None);
+    }
+    #[test]
+    fn exec_cmd_pipe() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out;
+        eval("add t1 R 99 2022-12-30; add t2 R 99 2022-12-30");
+        assert_eq!(lk.lock().borrow().db.len(), 2);
+        assert_eq!(eval("ls | grep -c t").data(), "2");
+        assert_eq!(
+            eval("ls . | tr t T | sort -r").data(),
+            "  2       T2 R 99 2022-12-30\n  1       T1 R 99 2022-12-30"
+        );
+        let file = std::env::temp_dir().join(format!("hel-redirect-{}", std::process::id()));
+        let path = file.to_str().unwrap();
+        assert_eq!(eval(&format!("ls t1 > {}", path)).output(), Vec::<String>::new());
+        eval(&format!("ls t2 >> {}", path));
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "  1       t1 R 99 2022-12-30\n  1       t2 R 99 2022-12-30\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&file).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(
+            eval("ls t1 > $HEL_UNDEFINED_VAR/f").output(),
+            vec!["error: failed to expand file name $HEL_UNDEFINED_VAR/f: error looking key 'HEL_UNDEFINED_VAR' up: environment variable not found"]
+        );
+        assert_eq!(eval("enc nope | cat").output(), vec!["error: name nope not found".to_string()]);
+        assert!(LKEval::news(command_parser::cmd("ls; quit; ls").unwrap(), lk.clone()).eval().quit);
+    }
+    #[test]
+    fn exec_cmd_show() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.output();
+        eval("add \"my folder\" R 99 2022-12-30; add t1 10N 98 2022-12-30 me@example.com ^\"my folder\"");
+        eval("rename t1 work; ls t1");
+        let show = eval("show work");
+        assert_eq!(show[0], "name:     t1");
+        assert_eq!(show[1], "alias:    work");
+        assert_eq!(show[3], "length:   10");
+        assert!(show[6].starts_with("date:     2022-12-30, ") && show[6].ends_with(" days old"));
+        assert_eq!(show[7], "comment:  me@example.com");
+        assert_eq!(show[8], "folders:  \"my folder\" > /");
+        assert_eq!(show[9], "secrets:  -");
+        assert_eq!(show[10], "correct:  unknown, no cached secret");
+        assert_eq!(show[11], "ls key:   1");
+        lk.lock().borrow_mut().secrets.insert("/".to_string(), "a".to_string());
+        eval("correct t1");
+        let show = eval("show 1");
+        assert_eq!(show[9], "secrets:  \"my folder\", /");
+        assert_eq!(show[10], "correct:  yes");
+        lk.lock().borrow_mut().secrets.remove("my folder");
+        let show = eval("show 1");
+        assert_eq!((show[9].as_str(), show[10].as_str()), ("secrets:  /", "correct:  yes"));
+        assert_eq!(lk.lock().borrow().secrets.keys().collect::<Vec<_>>(), vec!["/"]);
+        assert_eq!(eval("show nope"), vec!["error: name nope not found".to_string()]);
+    }
+    #[test]
+    fn exec_cmd_find() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.output();
+        eval("add github R 99 2022-12-30; add gitlab R 99 2022-12-30 work; add \"my bank\" N 99 2021-01-01 git");
+        assert_eq!(eval("find gthb"), vec!["  1       github R 99 2022-12-30".to_string()]);
+        assert_eq!(
+            eval("find git"),
+            vec![
+                "  1       github R 99 2022-12-30",
+                "  2       gitlab R 99 2022-12-30 work",
+                "  3       \"my bank\" N 99 2021-01-01 git"
+            ]
+        );
+        assert_eq!(eval("find bank 2021"), vec!["  1       \"my bank\" N 99 2021-01-01 git".to_string()]);
+        assert_eq!(eval("find (foo"), Vec::<String>::new());
+        assert!(lk.lock().borrow().ls.is_empty());
+        eval("find wrk");
+        assert_eq!(lk.lock().borrow().ls["1"].lock().borrow().name, "gitlab");
+    }
+    #[test]
+    fn exec_cmd_tree() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.output();
+        eval("add bank R 99 2022-12-30; add pin 6D 99 2022-12-30 ^bank; add web 10N 99 2022-12-30 ^bank");
+        eval("add \"my card\" 4D 99 2022-12-30 ^pin; add github R 99 2022-12-30");
+        lk.lock().borrow_mut().secrets.insert("pin".to_string(), "a".to_string());
+        assert_eq!(
+            eval("tree"),
+            vec![
+                "/ (5)",
+                "├── 1 bank (3)",
+                "│   ├── 2 pin (1) unlocked",
+                "│   │   └── 3 \"my card\"",
+                "│   └── 4 web",
+                "└── 5 github",
+            ]
+        );
+        assert_eq!(lk.lock().borrow().ls["3"].lock().borrow().name, "my card");
+        assert_eq!(eval("tree 2"), vec!["1 pin (1) unlocked", "└── 2 \"my card\""]);
+        assert_eq!(eval("tree nope"), vec!["error: folder nope not found"]);
+    }
+    #[test]
+    fn exec_cmd_cd() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.output();
+        eval("add bank R 99 2022-12-30; add pin 6D 99 2022-12-30 ^bank; add card 4D 99 2022-12-30 ^pin");
+        eval("add github R 99 2022-12-30");
+        let read = LKRead::new(Editor::new(), "> ".to_string(), lk.clone());
+        assert_eq!(read.current_prompt(), "> ");
+        eval("cd bank");
+        assert_eq!(eval("pwd"), vec!["/bank"]);
+        assert_eq!(read.current_prompt(), "/bank> ");
+        assert_eq!(eval("ls"), vec!["  1       pin 6D 99 2022-12-30 ^bank"]);
+        eval("add web 10N 99 2022-12-30");
+        assert_eq!(lk.lock().borrow().db["web"].lock().borrow().parent.as_ref().unwrap().lock().borrow().name, "bank");
+        eval("cd pin");
+        assert_eq!(eval("pwd"), vec!["/bank/pin"]);
+        assert_eq!(eval("show ../web")[0], "name:     web");
+        eval("cd ..; cd /bank/pin/card");
+        assert_eq!(eval("pwd"), vec!["/bank/pin/card"]);
+        assert_eq!(eval("cd nope"), vec!["error: folder nope not found"]);
+        assert_eq!(eval("cd /github/pin"), vec!["error: folder /github/pin not found"]);
+        eval("cd");
+        assert_eq!(eval("pwd"), vec!["/"]);
+        eval("cd bank; rm bank");
+        assert!(lk.lock().borrow().cwd.is_none());
+        eval("add shop R 99 2022-12-30; add cart R 99 2022-12-30 ^shop; add item R 99 2022-12-30 ^cart");
+        eval("cd /shop/cart/item; rm github");
+        assert_eq!(eval("pwd"), vec!["/shop/cart/item"]);
+        eval("rm shop");
+        assert!(lk.lock().borrow().cwd.is_none());
+    }
+    #[test]
+    fn exec_cmd_defaults() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.output();
+        let entry = |name: &str| lk.lock().borrow().db[name].lock().borrow().to_string();
+        eval("add bank R 99 2022-12-30; add pin R 99 2022-12-30 ^bank");
+        eval("defaults / seq=98; defaults bank len=6 mode=D; defaults bank mode=H; defaults pin prefix=#P len=4");
+        assert_eq!(eval("defaults bank"), vec!["len=6 mode=H"]);
+        assert_eq!(eval("defaults nope"), vec!["error: folder nope not found"]);
+        eval("add top; cd bank; add card; cd pin; add code");
+        let today = Date::now();
+        assert_eq!(entry("top"), format!("      top C 98 {}", today));
+        assert_eq!(entry("card"), format!("      card 6H 98 {} ^bank", today));
+        assert_eq!(entry("code"), format!("   #P code 4H 98 {} ^pin", today));
+        let dump = eval("dump");
+        assert_eq!(dump[..3], ["defaults / seq=98", "add       bank R 99 2022-12-30", "defaults bank len=6 mode=H"]);
+        assert_eq!(dump[3], format!("add       card 6H 98 {} ^bank", today));
+        assert_eq!(eval("undefaults bank"), vec!["Removed defaults for bank"]);
+        assert_eq!(eval("undefaults bank"), vec!["error: defaults for bank not found"]);
+        eval("cd /; ls pin.R");
+        assert_eq!(eval("undefaults 1"), vec!["Removed defaults for pin"]);
+        assert_eq!(eval("defaults pin"), vec!["no defaults for pin"]);
+        assert_eq!(eval("undefaults 1"), vec!["error: defaults for pin not found"]);
+        assert_eq!(eval("undefaults /"), vec!["Removed defaults for /"]);
+        assert!(command_parser::cmd("defaults bank date=now").is_err());
+    }
+    #[test]
+    fn exec_cmd_foreach() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.output();
+        let mode = |name: &str| lk.lock().borrow().db[name].lock().borrow().mode.to_string();
+        eval("add a1 R 99 2022-12-30; add a2 R 98 2022-12-30; add \"b 3\" R 99 2022-12-30; add c4 H 99 2022-12-30");
+        assert_eq!(
+            eval("foreach -n mode:R edit {name} mode=C"),
+            vec!["  edit a1 mode=C", "  edit a2 mode=C", "  edit \"b 3\" mode=C"]
+        );
+        assert_eq!(mode("a1"), "R");
+        let output = eval("foreach -y \"mode:R seq:99\" edit {name} mode=C");
+        assert!(output.contains(&"changed b 3".to_string()));
+        assert_eq!((mode("a1"), mode("a2"), mode("b 3"), mode("c4")), ("C".into(), "R".into(), "C".into(), "H".into()));
+        assert_eq!(eval("foreach -y name:~^c rm {name}"), vec!["  rm c4", "removed c4"]);
+        assert_eq!(eval("foreach nope rm {name}"), vec!["error: no entries match nope"]);
+        assert_eq!(eval("rm a3"), vec!["error: password a3 not found", "did you mean a1?"]);
+        assert_eq!(eval("rm zzz"), vec!["error: password zzz not found"]);
+        assert_eq!(
+            eval("foreach -y a1 edit {name} seq=x"),
+            vec!["error at 1:13: expected a sequence number", "  edit a1 seq=x", "              ^"]
+        );
+        assert_eq!(
+            command_parser::cmd("foreach ^work enc {name}"),
+            Ok(Command::Foreach("^work".to_string(), "enc {name}".to_string(), None))
+        );
+    }
+    #[test]
+    fn exec_cmd_gen_placeholders() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.output();
+        let names = || {
+            let mut names = lk.lock().borrow().ls.values().map(|p| p.lock().borrow().name.clone()).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        eval("pass / secret");
+        eval("gen20 acct{n}x{mm}");
+        assert_eq!(names().len(), 20);
+        assert!(names().iter().all(|n| Regex::new(r"^acct[1-9]x(0[1-9]|1[0-2])$").unwrap().is_match(n)));
+        eval("gen github{yyyy}");
+        let year = Date::now().year();
+        assert_eq!(names(), (year - 9..=year).map(|y| format!("github{}", y)).collect::<Vec<_>>());
+        eval("gen id{rand:4}-{alpha:2}G");
+        assert_eq!(names().len(), 9);
+        assert!(names().iter().all(|n| Regex::new(r"^id[0-9]{4}-[a-z]{2}[1-9]$").unwrap().is_match(n)));
+        assert_eq!(
+            eval("gen x{foo}"),
+            vec!["error: unknown placeholder {foo}, use {n}, {yyyy}, {mm}, {rand:N} or {alpha:N}"]
+        );
+        assert_eq!(eval("gen x{nnn}{nn}"), vec!["error: the placeholders expand to more than 1000 names"]);
+        assert_eq!(eval("gen x{nnn}GGG"), vec!["error: the name expands to more than 1000 names"]);
+        assert_eq!(eval("gen x{mm}GGG"), vec!["error: the name expands to more than 1000 names"]);
+    }
+    #[test]
+    fn exec_cmd_error_kinds() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| match command_parser::cmd(line) {
+            Ok(cmd) => LKEval::news(cmd, lk.clone()).eval(),
+            Err(e) => LKEval::news(Command::Error(LKErr::ParseError(line.to_string(), e)), lk.clone()).eval(),
+        };
+        eval("add t1 R 99 2022-12-30");
+        assert_eq!(eval("ls").error, None);
+        assert_eq!(eval("ls").exit_code(), 0);
+        assert_eq!(eval("rm t9").error, Some(LKErr::NotFound("password t9 not found".to_string())));
+        assert_eq!(eval("add t1 R 99 now").error, Some(LKErr::AlreadyExists("password t1 already exist".to_string())));
+        assert_eq!(eval("enc t1").error, Some(LKErr::MasterMissing("master for t1 not found".to_string())));
+        assert_eq!(eval("ls (x").exit_code(), 1);
+        assert_eq!(eval("frobnicate").exit_code(), 2);
+        assert_eq!(eval("rm t9; ls; enc t1").exit_code(), 3);
+    }
+    #[test]
+    fn exec_cmd_json() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let json = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.json();
+        json("add gh R 99 2022-12-30 me@home; add t2 C 99 2022-12-30 ^gh; rename t2 work; pass / secret");
+        assert_eq!(
+            json("ls name:gh"),
+            r#"{"type":"entries","entries":[{"key":"1","name":"gh","alias":null,"prefix":null,"length":null,"mode":"R","seq":99,"date":"2022-12-30","comment":"me@home","parent":null}]}"#
+        );
+        assert!(json("ls name:t2").contains(r#""alias":"work","prefix":null,"length":null,"mode":"C""#));
+        assert!(json("ls name:t2").ends_with(r#""parent":"gh"}]}"#));
+        let enc = LKEval::news(command_parser::cmd("enc gh").unwrap(), lk.clone()).eval().out;
+        assert_eq!(json("enc gh"), format!(r#"{{"type":"password","name":"gh","password":"{}"}}"#, enc.data()));
+        assert!(json("gen3 ghG").starts_with(r#"{"type":"gen","passwords":[{"password":""#));
+        assert_eq!(
+            json("rm nope"),
+            r#"{"type":"error","kind":"not_found","code":3,"message":"password nope not found"}"#
+        );
+        assert_eq!(json("pwd"), r#"{"type":"lines","lines":["/"]}"#);
+        lk.lock().borrow_mut().settings.set("output", "json").unwrap();
+        assert_eq!(json("ls name:gh | wc -l"), r#"{"type":"lines","lines":["0"]}"#);
+    }
+    #[test]
+    fn exec_cmd_type() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval();
+        eval("add t1 R 99 2022-12-30 no user here");
+        assert_eq!(eval("type -u t1").out.output(), vec!["error: no user=NAME in the comment of t1"]);
+        assert_eq!(eval("type t2").exit_code(), 3);
+        assert_eq!(command_parser::cmd("type -u \"my bank\""), Ok(Command::Type("my bank".to_string(), true)));
+        assert_eq!(Command::Type("t1".to_string(), false).to_string(), "type t1");
+    }
+
+    #[test]
+    fn exec_cmd_settings() {
+        let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+        let eval = |line: &str| LKEval::news(command_parser::cmd(line).unwrap(), lk.clone()).eval().out.output();
+        assert_eq!(
+            eval("settings"),
+            vec![
+                "mode   C",
+                "seq    99",
+                "gen    10",
+                "sort   name",
+                "date   iso",
+                "output text",
+                "clip   clipboard",
+                "clear  30",
+                "delay  2"
+            ]
+        );
+        assert_eq!(eval("set gen 0"), vec!["error: gen must be at least 1"]);
+        assert_eq!(eval("set mode X"), vec!["error: unknown mode X"]);
+        assert_eq!(
+            eval("set color red"),
+            vec!["error: unknown setting color, use one of mode, seq, gen, sort, date, output, clip, clear, delay"]
+        );
+        assert_eq!(
+            eval("get nope"),
+            vec!["error: unknown setting nope, use one of mode, seq, gen, sort, date, output, clip, clear, delay"]
+        );
+        {
+            let lk = lk.lock();
+            let mut lk = lk.borrow_mut();
+            lk.settings.set("mode", "h").unwrap();
+            lk.settings.set("seq", "42").unwrap();
+            lk.settings.set("sort", "date").unwrap();
+            lk.settings.set("date", "age").unwrap();
+        }
+        assert_eq!(eval("get mode"), vec!["H"]);
+        eval("add zeta R 99 2022-12-30; add alpha R 99 2023-01-30; add top");
+        assert_eq!(lk.lock().borrow().db["top"].lock().borrow().to_string(), format!("      top H 42 {}", Date::now()));
+        let ls = eval("ls");
+        assert!(ls[0].starts_with("  1       zeta R 99 ") && ls[0].ends_with('d'));
+        assert!(ls[1].starts_with("  2       alpha R 99 "));
+        assert_eq!(command_parser::cmd("gen foo"), Ok(Command::GenDefault(None, "foo".to_string())));
+        assert_eq!(command_parser::cmd("set mode N"), Ok(Command::Set("mode".to_string(), "N".to_string())));
+        assert_eq!(command_parser::cmd("get"), command_parser::cmd("settings"));
+
+        let file = std::env::temp_dir().join(format!("hel-settings-{}", std::process::id()));
+        std::fs::write(&file, "add t1\nset gen 5\nset sort name\n").unwrap();
+        let (set, out) = (LKEval::news(Command::Noop, lk.clone()), LKOut::new());
+        set.set_and_save(&out, None, "gen", "7");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "add t1\nset gen 5\nset sort name\n");
+        set.set_and_save(&out, Some(&file), "gen", "12");
+        set.set_and_save(&out, Some(&file), "clear", "10");
+        set.set_and_save(&out, Some(&file), "sort", "date");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "add t1\nset gen 12\nset sort name\nset clear 10\n");
+        assert_eq!(out.output(), Vec::<String>::new());
+        assert_eq!(eval("get gen"), vec!["12"]);
+        std::fs::remove_file(&file).unwrap();
+    }
 }
@@ -1,140 +1,246 @@
-use crate::password::{Comment, Name, PasswordRef};
+use crate::password::{Comment, Length, Name, PasswordRef, Prefix, Seq};
+use crate::utils::date::Date;
 use num_integer::Integer;
 use parking_lot::Mutex;
 use parking_lot::ReentrantMutex;
 use std::cell::RefCell;
 use std::fmt;
-use std::path::Path;
 use std::sync::Arc;
 
+use crate::config::Config;
 use crate::lk::LK;
-use crate::parser::command_parser;
+use crate::output::LKResult;
+use crate::parser::{command_parser, parse_error_message};
 use crate::repl::{LKEval, LKRead};
 use crate::utils::editor::{password, Editor};
-use crate::utils::home;
+use crate::utils::{quote_name, quote_text};
 
+// the config is loaded once on first use, so the environment is read as late as possible
 lazy_static! {
-    pub static ref HISTORY_FILE: Box<Path> = {
-        match std::env::var("HEL_HISTORY") {
-            Ok(v) => Path::new(shellexpand::full(&v).unwrap().into_owned().as_str()).to_path_buf().into_boxed_path(),
-            _ => home::dir().join(".hel_history").into_boxed_path(),
-        }
-    };
-    pub static ref PROMPT_SETTING: String = {
-        match std::env::var("HEL_PROMPT") {
-            Ok(v) => v,
-            _ => "> ".to_string(),
-        }
-    };
-    pub static ref INIT_FILE: Box<Path> = {
-        match std::env::var("HEL_INIT") {
-            Ok(v) => Path::new(shellexpand::full(&v).unwrap().into_owned().as_str()).to_path_buf().into_boxed_path(),
-            _ => home::dir().join(".helrc").into_boxed_path(),
-        }
-    };
-    pub static ref CORRECT_FILE: Box<Path> = {
-        match std::env::var("HEL_CORRECT") {
-            Ok(v) => Path::new(shellexpand::full(&v).unwrap().into_owned().as_str()).to_path_buf().into_boxed_path(),
-            _ => home::dir().join(".hel_correct").into_boxed_path(),
-        }
-    };
-    pub static ref DUMP_FILE: Box<Path> = {
-        match std::env::var("HEL_DUMP") {
-            Ok(v) => Path::new(shellexpand::full(&v).unwrap().into_owned().as_str()).to_path_buf().into_boxed_path(),
-            _ => home::dir().join(".hel_dump").into_boxed_path(),
-        }
-    };
+    pub static ref CONFIG: Config = Config::load();
 }
 
-#[derive(thiserror::Error, Debug, PartialEq)]
-pub enum LKErr<'a> {
-    #[error("Error: {0}")]
-    Error(&'a str),
-    #[error("Error: end of file")]
+#[derive(thiserror::Error, Debug, PartialEq, Clone)]
+pub enum LKErr {
+    #[error("error: {0}")]
+    Error(String),
+    #[error("error: end of file")]
     EOF,
-    #[error("Failed to read the line: {0}")]
+    #[error("{0}")]
     ReadError(String),
-    #[error("Failed to parse: {0}")]
-    ParseError(peg::error::ParseError<peg::str::LineCol>),
+    #[error("{}", parse_error_message(.0, .1).join("\n"))]
+    ParseError(String, peg::error::ParseError<peg::str::LineCol>),
+    #[error("error: {0}")]
+    NotFound(String),
+    #[error("error: {0}")]
+    AlreadyExists(String),
+    #[error("error: {0}")]
+    MasterMissing(String),
+    #[error("error: {0}")]
+    Io(String),
+    #[error("error: {0}")]
+    Config(String),
+}
+
+impl LKErr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LKErr::Error(_) => "error",
+            LKErr::EOF => "eof",
+            LKErr::ReadError(_) => "read",
+            LKErr::ParseError(..) => "parse",
+            LKErr::NotFound(_) => "not_found",
+            LKErr::AlreadyExists(_) => "already_exists",
+            LKErr::MasterMissing(_) => "master_missing",
+            LKErr::Config(_) => "config",
+            LKErr::Io(_) => "io",
+        }
+    }
+
+    /// Process exit status for the error: 1 for other errors, 2 parse error, 3 not found, 4 already
+    /// exists, 5 master missing, 6 for I/O errors and 7 for an invalid config file.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LKErr::Error(_) | LKErr::EOF => 1,
+            LKErr::ParseError(..) => 2,
+            LKErr::NotFound(_) => 3,
+            LKErr::AlreadyExists(_) => 4,
+            LKErr::MasterMissing(_) => 5,
+            LKErr::Io(_) | LKErr::ReadError(_) => 6,
+            LKErr::Config(_) => 7,
+        }
+    }
 }
 
 #[derive(Debug)]
-pub enum Command<'a> {
+pub enum Command {
     Add(PasswordRef),
+    AddDefault(Name),
     Keep(Name),
     Ls(String),
     Ld(String),
+    Find(String),
     Mv(Name, Name),
     Rm(Name),
     Enc(Name),
-    Gen(u32, PasswordRef),
+    Gen(Option<u32>, PasswordRef),
+    GenDefault(Option<u32>, Name),
     Pass(Name, Option<String>),
     UnPass(Name),
     Correct(Name),
     Uncorrect(Name),
     PasteBuffer(String),
+    Type(Name, bool),
     Source(String),
     Dump(Option<String>),
     Comment(Name, Comment),
-    Error(LKErr<'a>),
+    Edit(Name, Vec<EditField>, bool),
+    Rename(Name, Option<Name>),
+    Show(Name),
+    Tree(Option<Name>),
+    Cd(Option<Name>),
+    Pwd,
+    Defaults(Name, Vec<EditField>),
+    UnDefaults(Name),
+    Foreach(String, String, Option<bool>),
+    Set(String, String),
+    Get(Option<String>),
+    Config,
+    Chain(Vec<Command>),
+    Pipe(Box<Command>, String),
+    Redirect(Box<Command>, String, bool),
+    Error(LKErr),
     Noop,
-    Help,
+    Help(Option<String>),
     Quit,
 }
 
-impl<'a> PartialEq for Command<'a> {
+impl PartialEq for Command {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Command::Add(s), Command::Add(o)) => *s.lock() == *o.lock(),
+            (Command::AddDefault(s), Command::AddDefault(o)) => s == o,
             (Command::Keep(s), Command::Keep(o)) => s == o,
             (Command::Ls(s), Command::Ls(o)) => s == o,
             (Command::Ld(s), Command::Ld(o)) => s == o,
+            (Command::Find(s), Command::Find(o)) => s == o,
             (Command::Mv(a, b), Command::Mv(x, y)) => a == x && b == y,
             (Command::Rm(s), Command::Rm(o)) => s == o,
             (Command::Enc(s), Command::Enc(o)) => s == o,
             (Command::Gen(a, b), Command::Gen(x, y)) => a == x && *b.lock() == *y.lock(),
+            (Command::GenDefault(a, b), Command::GenDefault(x, y)) => a == x && b == y,
             (Command::Pass(a, b), Command::Pass(x, y)) => a == x && b == y,
             (Command::UnPass(s), Command::UnPass(o)) => s == o,
             (Command::Correct(s), Command::Correct(o)) => s == o,
             (Command::Uncorrect(s), Command::Uncorrect(o)) => s == o,
             (Command::PasteBuffer(s), Command::PasteBuffer(o)) => s == o,
+            (Command::Type(a, b), Command::Type(x, y)) => a == x && b == y,
             (Command::Source(s), Command::Source(o)) => s == o,
             (Command::Dump(s), Command::Dump(o)) => s == o,
             (Command::Comment(a, b), Command::Comment(x, y)) => a == x && b == y,
+            (Command::Edit(a, b, c), Command::Edit(x, y, z)) => a == x && b == y && c == z,
+            (Command::Rename(a, b), Command::Rename(x, y)) => a == x && b == y,
+            (Command::Show(s), Command::Show(o)) => s == o,
+            (Command::Tree(s), Command::Tree(o)) => s == o,
+            (Command::Cd(s), Command::Cd(o)) => s == o,
+            (Command::Pwd, Command::Pwd) => true,
+            (Command::Defaults(a, b), Command::Defaults(x, y)) => a == x && b == y,
+            (Command::UnDefaults(s), Command::UnDefaults(o)) => s == o,
+            (Command::Foreach(a, b, c), Command::Foreach(x, y, z)) => a == x && b == y && c == z,
+            (Command::Set(a, b), Command::Set(x, y)) => a == x && b == y,
+            (Command::Get(s), Command::Get(o)) => s == o,
+            (Command::Config, Command::Config) => true,
+            (Command::Chain(s), Command::Chain(o)) => s == o,
+            (Command::Pipe(a, b), Command::Pipe(x, y)) => a == x && b == y,
+            (Command::Redirect(a, b, c), Command::Redirect(x, y, z)) => a == x && b == y && c == z,
             (Command::Error(s), Command::Error(o)) => s == o,
             (Command::Noop, Command::Noop) => true,
-            (Command::Help, Command::Help) => true,
+            (Command::Help(s), Command::Help(o)) => s == o,
             (Command::Quit, Command::Quit) => true,
             _ => false,
         }
     }
 }
 
-impl<'a> std::fmt::Display for Command<'a> {
+impl std::fmt::Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Command::Add(s) => write!(f, "add {}", s.lock().borrow().to_string().trim()),
-            Command::Keep(s) => write!(f, "keep {}", s),
+            Command::AddDefault(s) => write!(f, "add {}", quote_name(s)),
+            Command::Keep(s) => write!(f, "keep {}", quote_name(s)),
             Command::Ls(s) => write!(f, "ls {}", s),
             Command::Ld(s) => write!(f, "ld {}", s),
-            Command::Mv(a, b) => write!(f, "mv {} {}", a, b),
-            Command::Rm(s) => write!(f, "rm {}", s),
-            Command::Enc(s) => write!(f, "enc {}", s),
-            Command::Gen(a, b) => write!(f, "gen{} {}", a, b.lock().borrow().to_string().trim()),
-            Command::Pass(a, None) => write!(f, "pass {}", a),
-            Command::Pass(a, Some(b)) => write!(f, "pass {} {}", a, b),
-            Command::UnPass(s) => write!(f, "unpass {}", s),
-            Command::Correct(s) => write!(f, "correct {}", s),
-            Command::Uncorrect(s) => write!(f, "uncorrect {}", s),
+            Command::Find(s) => write!(f, "find {}", s),
+            Command::Mv(a, b) => write!(f, "mv {} {}", quote_name(a), quote_name(b)),
+            Command::Rm(s) => write!(f, "rm {}", quote_name(s)),
+            Command::Enc(s) => write!(f, "enc {}", quote_name(s)),
+            Command::Gen(a, b) => {
+                write!(
+                    f,
+                    "gen{} {}",
+                    a.map(|n| n.to_string()).unwrap_or_default(),
+                    b.lock().borrow().to_string().trim()
+                )
+            }
+            Command::GenDefault(a, b) => {
+                write!(f, "gen{} {}", a.map(|n| n.to_string()).unwrap_or_default(), quote_name(b))
+            }
+            Command::Pass(a, None) => write!(f, "pass {}", quote_name(a)),
+            Command::Pass(a, Some(b)) => write!(f, "pass {} {}", quote_name(a), b),
+            Command::UnPass(s) => write!(f, "unpass {}", quote_name(s)),
+            Command::Correct(s) => write!(f, "correct {}", quote_name(s)),
+            Command::Uncorrect(s) => write!(f, "uncorrect {}", quote_name(s)),
             Command::PasteBuffer(s) => write!(f, "pb {}", s),
+            Command::Type(s, u) => write!(f, "type{} {}", if *u { " -u" } else { "" }, quote_name(s)),
             Command::Source(s) => write!(f, "source {}", s),
             Command::Dump(None) => write!(f, "dump"),
             Command::Dump(Some(s)) => write!(f, "dump {}", s),
-            Command::Comment(a, None) => write!(f, "comment {}", a),
-            Command::Comment(a, Some(b)) => write!(f, "comment {} {}", a, b),
+            Command::Comment(a, None) => write!(f, "comment {}", quote_name(a)),
+            Command::Comment(a, Some(b)) => write!(f, "comment {} {}", quote_name(a), quote_text(b)),
+            Command::Edit(a, b, c) => {
+                write!(f, "edit{} {}", if *c { " -y" } else { "" }, quote_name(a))?;
+                for field in b {
+                    write!(f, " {}", field)?;
+                }
+                Ok(())
+            }
+            Command::Rename(a, None) => write!(f, "rename {}", quote_name(a)),
+            Command::Rename(a, Some(b)) => write!(f, "rename {} {}", quote_name(a), quote_name(b)),
+            Command::Show(s) => write!(f, "show {}", quote_name(s)),
+            Command::Tree(None) => write!(f, "tree"),
+            Command::Tree(Some(s)) => write!(f, "tree {}", quote_name(s)),
+            Command::Cd(None) => write!(f, "cd"),
+            Command::Cd(Some(s)) => write!(f, "cd {}", quote_name(s)),
+            Command::Pwd => write!(f, "pwd"),
+            Command::Defaults(a, b) => {
+                write!(f, "defaults {}", quote_name(a))?;
+                for field in b {
+                    write!(f, " {}", field)?;
+                }
+                Ok(())
+            }
+            Command::UnDefaults(s) => write!(f, "undefaults {}", quote_name(s)),
+            Command::Foreach(a, b, c) => {
+                let flag = match c {
+                    Some(true) => " -y",
+                    Some(false) => " -n",
+                    None => "",
+                };
+                write!(f, "foreach{} {} {}", flag, quote_name(a), b)
+            }
+            Command::Set(a, b) => write!(f, "set {} {}", a, b),
+            Command::Get(None) => write!(f, "get"),
+            Command::Get(Some(s)) => write!(f, "get {}", s),
+            Command::Config => write!(f, "config"),
+            Command::Chain(s) => {
+                write!(f, "{}", s.iter().map(|c| c.to_string()).collect::<Vec<String>>().join("; "))
+            }
+            Command::Pipe(a, b) => write!(f, "{} | {}", a, b),
+            Command::Redirect(a, b, c) => write!(f, "{} {} {}", a, if *c { ">>" } else { ">" }, quote_name(b)),
             Command::Error(s) => write!(f, "error {}", s),
             Command::Noop => write!(f, "noop"),
-            Command::Help => write!(f, "help"),
+            Command::Help(None) => write!(f, "help"),
+            Command::Help(Some(s)) => write!(f, "help {}", s),
             Command::Quit => write!(f, "quit"),
         }
     }
@@ -176,10 +282,148 @@ impl std::fmt::Display for Mode {
     }
 }
 
+impl std::str::FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "R" => Ok(Mode::Regular),
+            "UR" | "U" => Ok(Mode::RegularUpcase),
+            "N" => Ok(Mode::NoSpace),
+            "UN" => Ok(Mode::NoSpaceUpcase),
+            "C" => Ok(Mode::NoSpaceCamel),
+            "H" => Ok(Mode::Hex),
+            "UH" => Ok(Mode::HexUpcase),
+            "B" => Ok(Mode::Base64),
+            "UB" => Ok(Mode::Base64Upcase),
+            "D" => Ok(Mode::Decimal),
+            _ => Err(format!("unknown mode {}", s)),
+        }
+    }
+}
+
+/// Session defaults changed with `set`, the init file keeps them between sessions.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Settings {
+    pub mode: Mode,
+    pub seq: Seq,
+    pub gen: u32,
+    pub sort: String,
+    pub date: String,
+    pub output: String,
+    pub clip: String,
+    pub clear: u32,
+    pub delay: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mode: Mode::NoSpaceCamel,
+            seq: 99,
+            gen: 10,
+            sort: "name".to_string(),
+            date: "iso".to_string(),
+            output: "text".to_string(),
+            clip: "clipboard".to_string(),
+            clear: 30,
+            delay: 2,
+        }
+    }
+}
+
+impl Settings {
+    pub const KEYS: [&'static str; 9] = ["mode", "seq", "gen", "sort", "date", "output", "clip", "clear", "delay"];
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "mode" => Some(self.mode.to_string()),
+            "seq" => Some(self.seq.to_string()),
+            "gen" => Some(self.gen.to_string()),
+            "sort" => Some(self.sort.clone()),
+            "date" => Some(self.date.clone()),
+            "output" => Some(self.output.clone()),
+            "clip" => Some(self.clip.clone()),
+            "clear" => Some(self.clear.to_string()),
+            "delay" => Some(self.delay.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = || value.parse::<u32>().map_err(|_| format!("{} must be a number, not {}", key, value));
+        match key {
+            "mode" => self.mode = value.parse()?,
+            "seq" => self.seq = number()?,
+            "gen" => match number()? {
+                0 => return Err("gen must be at least 1".to_string()),
+                n => self.gen = n,
+            },
+            "sort" => match value {
+                "name" | "date" => self.sort = value.to_string(),
+                _ => return Err(format!("sort must be name or date, not {}", value)),
+            },
+            "date" => match value {
+                "iso" | "age" => self.date = value.to_string(),
+                _ => return Err(format!("date must be iso or age, not {}", value)),
+            },
+            "output" => match value {
+                "text" | "json" => self.output = value.to_string(),
+                _ => return Err(format!("output must be text or json, not {}", value)),
+            },
+            "clip" => match value {
+                "clipboard" | "primary" => self.clip = value.to_string(),
+                _ => return Err(format!("clip must be clipboard or primary, not {}", value)),
+            },
+            "clear" => self.clear = number()?,
+            "delay" => self.delay = number()?,
+            _ => return Err(format!("unknown setting {}, use one of {}", key, Self::KEYS.join(", "))),
+        }
+        Ok(())
+    }
+
+    pub fn json(&self) -> bool {
+        self.output == "json"
+    }
+
+    /// Format a date for listings, `age` shows the number of days since it.
+    pub fn show_date(&self, date: &Date) -> String {
+        match self.date.as_str() {
+            "age" => format!("{}d", date.days_until(&Date::now())),
+            _ => date.to_string(),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum EditField {
+    Prefix(Prefix),
+    Length(Length),
+    Mode(Mode),
+    Seq(Seq),
+    Date(Date),
+    Comment(Comment),
+}
+
+impl std::fmt::Display for EditField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditField::Prefix(p) => write!(f, "prefix={}", p.as_deref().map(quote_name).unwrap_or_default()),
+            EditField::Length(l) => write!(f, "len={}", l.map(|l| l.to_string()).unwrap_or_default()),
+            EditField::Mode(m) => write!(f, "mode={}", m),
+            EditField::Seq(s) => write!(f, "seq={}", s),
+            EditField::Date(d) => write!(f, "date={}", d),
+            EditField::Comment(c) => write!(f, "comment={}", c.as_deref().map(quote_name).unwrap_or_default()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct LKOut {
     pub out: Option<Arc<Mutex<Vec<String>>>>,
     pub err: Option<Arc<Mutex<Vec<String>>>>,
+    pub error: Arc<Mutex<Option<LKErr>>>,
+    pub result: Arc<Mutex<Option<LKResult>>>,
 }
 
 impl LKOut {
@@ -187,6 +431,8 @@ impl LKOut {
         Self {
             out: Some(Arc::new(Mutex::new(vec![]))),
             err: Some(Arc::new(Mutex::new(vec![]))),
+            error: Arc::new(Mutex::new(None)),
+            result: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -199,7 +445,12 @@ impl LKOut {
             Some(v) => Some(v.clone()),
             None => None,
         };
-        Self { out: o, err: e }
+        Self {
+            out: o,
+            err: e,
+            error: Arc::new(Mutex::new(None)),
+            result: Arc::new(Mutex::new(None)),
+        }
     }
 
     #[allow(dead_code)]
@@ -207,6 +458,8 @@ impl LKOut {
         Self {
             out: Some(Arc::new(Mutex::new(out))),
             err: Some(Arc::new(Mutex::new(err))),
+            error: Arc::new(Mutex::new(None)),
+            result: Arc::new(Mutex::new(None)),
         }
     }
 
@@ -220,6 +473,9 @@ impl LKOut {
     }
 
     pub fn copy_err(&self, out: &LKOut) {
+        if let Some(error) = self.error() {
+            out.set_error(error);
+        }
         if !self.err.is_some() {
             return;
         }
@@ -293,6 +549,41 @@ impl LKOut {
             self.err.as_ref().unwrap().lock().push(line);
         }
     }
+
+    /// Report a failed command, the first error is kept as the result of the command.
+    pub fn fail(&self, error: LKErr) {
+        for line in error.to_string().lines() {
+            self.e(line.to_string());
+        }
+        self.set_error(error);
+    }
+
+    fn set_error(&self, error: LKErr) {
+        self.error.lock().get_or_insert(error);
+    }
+
+    pub fn error(&self) -> Option<LKErr> {
+        self.error.lock().clone()
+    }
+
+    /// Remember the typed result of the command for the JSON output.
+    pub fn set_result(&self, result: LKResult) {
+        if self.active() {
+            *self.result.lock() = Some(result);
+        }
+    }
+
+    /// The command result as JSON, the error if the command failed, else the typed result or the lines.
+    pub fn json(&self) -> String {
+        let result = match (self.error(), self.result.lock().clone()) {
+            (Some(e), _) => LKResult::from(&e),
+            (None, Some(r)) => r,
+            (None, None) => LKResult::Lines {
+                lines: self.out.as_ref().map(|o| o.lock().clone()).unwrap_or_default(),
+            },
+        };
+        result.to_json()
+    }
 }
 
 impl PartialEq for LKOut {
@@ -350,11 +641,17 @@ impl fmt::Display for Radix {
     }
 }
 
-pub fn init() -> Option<LKRead> {
+/// Run the init file from `config` and set up the prompt, `history` off keeps the history file
+/// untouched from the first command on.
+pub fn init(config: &Config, history: bool) -> Option<LKRead> {
     let lk = Arc::new(ReentrantMutex::new(RefCell::new(LK::new())));
+    lk.lock().borrow_mut().config = config.clone();
+    let init_file = config.path("init");
     let editor = Editor::new();
+    editor.lock().set_state(lk.clone());
+    editor.lock().set_history(history);
 
-    match std::fs::read_to_string(INIT_FILE.to_str().unwrap()) {
+    match std::fs::read_to_string(&init_file) {
         Ok(script) => match command_parser::script(&script) {
             Ok(cmd_list) => {
                 for cmd in cmd_list {
@@ -364,18 +661,21 @@ pub fn init() -> Option<LKRead> {
                 }
             }
             Err(err) => {
-                LKEval::new(editor.clone(), Command::Error(LKErr::ParseError(err)), lk.clone(), password)
-                    .eval()
-                    .print();
+                LKEval::new(
+                    editor.clone(),
+                    Command::Error(LKErr::ParseError(script.to_string(), err)),
+                    lk.clone(),
+                    password,
+                )
+                .eval()
+                .print();
             }
         },
         Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
         Err(err) => {
             LKEval::new(
                 editor.clone(),
-                Command::Error(LKErr::Error(
-                    format!("Failed to read init file {:?}: {}", INIT_FILE.to_str(), err).as_str(),
-                )),
+                Command::Error(LKErr::Io(format!("Failed to read init file {:?}: {}", init_file.to_str(), err))),
                 lk.clone(),
                 password,
             )
@@ -383,7 +683,8 @@ pub fn init() -> Option<LKRead> {
             .print();
         }
     }
-    Some(LKRead::new(editor.clone(), PROMPT_SETTING.to_string(), lk.clone()))
+    let prompt = config.get("prompt").unwrap_or_default().to_string();
+    Some(LKRead::new(editor.clone(), prompt, lk.clone()))
 }
 
 #[cfg(test)]
@@ -393,15 +694,20 @@ mod tests {
     use crate::utils::date::Date;
     use std::io::{BufWriter, Write};
     use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
 
     #[test]
     fn test_env() {
-        std::env::set_var("HEL_HISTORY", "./test_history");
-        std::env::set_var("HEL_INIT", "./test_init");
-        std::env::set_var("HEL_DUMP", "./test_dump");
-        std::env::set_var("HEL_CORRECT", "./test_correct");
-        std::env::set_var("HEL_PB", "./test_pb");
-        std::env::set_var("HEL_PROMPT", "test> ");
+        let env = |v: &str| match v {
+            "HEL_HISTORY" => Some("./test_history".to_string()),
+            "HEL_INIT" => Some("./test_init".to_string()),
+            "HEL_DUMP" => Some("./test_dump".to_string()),
+            "HEL_CORRECT" => Some("./test_correct".to_string()),
+            "HEL_PB" => Some("./test_pb".to_string()),
+            "HEL_PROMPT" => Some("test> ".to_string()),
+            _ => None,
+        };
+        let config = Config::parse("", Path::new("/nonexistent/config.toml"), env);
 
         fn create_init() {
             let file = std::fs::File::create("test_init").unwrap();
@@ -441,7 +747,9 @@ mod tests {
         create_init();
         create_pb();
 
-        let lkread = init().unwrap();
+        assert!(init(&config, false).unwrap().state.lock().borrow().db.contains_key("t1"));
+        assert!(!std::path::Path::new("test_history").exists());
+        let lkread = init(&config, true).unwrap();
         assert_eq!(lkread.prompt, "test> ");
         assert_eq!(lkread.state.lock().borrow().db.contains_key("t1"), true);
 
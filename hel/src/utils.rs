@@ -1,13 +1,12 @@
 use shlex::split;
-use std::env;
-use std::ffi::OsString;
 use std::io;
 use std::io::Write;
 use std::process::{Command, Stdio};
+use unicode_normalization::UnicodeNormalization;
 
 pub mod date {
     use chrono::naive::NaiveDate;
-    use chrono::Local;
+    use chrono::{Datelike, Local};
 
     #[derive(PartialEq, Debug, Clone, Copy)]
     pub struct Date {
@@ -37,6 +36,14 @@ pub mod date {
         pub fn cmp(&self, other: &Self) -> core::cmp::Ordering {
             self.date.cmp(&other.date)
         }
+
+        pub fn days_until(&self, other: &Self) -> i64 {
+            (other.date - self.date).num_days()
+        }
+
+        pub fn year(&self) -> i32 {
+            self.date.year()
+        }
     }
 
     impl std::fmt::Display for Date {
@@ -89,28 +96,115 @@ pub mod home {
 
 #[cfg(unix)]
 pub mod editor {
+    use crate::completion::{complete, highlight, hint, Hint};
+    use crate::lk::LKRef;
     use crate::structs::LKErr;
+    use rustyline::completion::{Completer, Pair};
     use rustyline::error::ReadlineError;
     use rustyline::config::Configurer;
+    use rustyline::highlight::Highlighter;
+    use rustyline::hint::Hinter;
+    use rustyline::validate::Validator;
+    use rustyline::Context;
+    use std::borrow::Cow;
     use std::sync::Arc;
     use parking_lot::Mutex;
 
     pub type EditorRef = Arc<Mutex<Editor>>;
 
+    pub struct LKHelper {
+        state: Option<LKRef>,
+    }
+
+    impl Completer for LKHelper {
+        type Candidate = Pair;
+
+        fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+            let state = match &self.state {
+                Some(s) => s,
+                None => return Ok((pos, vec![])),
+            };
+            let (start, candidates) = complete(&state.lock().borrow(), line, pos);
+            Ok((
+                start,
+                candidates
+                    .into_iter()
+                    .map(|c| Pair {
+                        display: c.display,
+                        replacement: c.replacement,
+                    })
+                    .collect(),
+            ))
+        }
+    }
+
+    impl rustyline::hint::Hint for Hint {
+        fn display(&self) -> &str {
+            &self.display
+        }
+
+        fn completion(&self) -> Option<&str> {
+            self.completion.as_deref()
+        }
+    }
+
+    impl Hinter for LKHelper {
+        type Hint = Hint;
+
+        fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<Hint> {
+            match &self.state {
+                Some(s) => hint(&s.lock().borrow(), line, pos),
+                None => None,
+            }
+        }
+    }
+
+    impl Highlighter for LKHelper {
+        fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+            Cow::Owned(highlight(line))
+        }
+
+        fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+            Cow::Owned(format!("\x1b[2m{}\x1b[0m", hint))
+        }
+
+        fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+            true
+        }
+    }
+
+    impl Validator for LKHelper {}
+
+    impl rustyline::Helper for LKHelper {}
+
     #[derive(Debug)]
     pub struct Editor {
-        editor: rustyline::Editor<()>,
+        editor: rustyline::Editor<LKHelper>,
+        history: bool,
     }
 
     impl Editor {
         pub fn new() -> EditorRef {
-            let mut editor = rustyline::Editor::<()>::new().unwrap();
+            let mut editor = rustyline::Editor::<LKHelper>::new().unwrap();
             editor.set_max_history_size(10000);
+            editor.set_helper(Some(LKHelper { state: None }));
             Arc::new(Mutex::new(Self {
                 editor: editor,
+                history: true,
             }))
         }
 
+        pub fn set_state(&mut self, state: LKRef) {
+            if let Some(helper) = self.editor.helper_mut() {
+                helper.state = Some(state);
+            }
+        }
+
+        /// With the history disabled the history file is neither read nor written.
+        pub fn set_history(&mut self, enabled: bool) {
+            self.history = enabled;
+        }
+
         pub fn clear_history(&mut self) {
             self.editor.clear_history();
         }
@@ -119,25 +213,31 @@ pub mod editor {
             self.editor.add_history_entry(entry);
         }
 
-        pub fn load_history<'a>(&mut self, fname: &str) -> Result<(), LKErr<'a>> {
+        pub fn load_history(&mut self, fname: &str) -> Result<(), LKErr> {
+            if !self.history {
+                return Ok(());
+            }
             match self.editor.load_history(&fname) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(LKErr::Error("failed to read history file")),
+                Err(_) => Err(LKErr::Io("failed to read history file".to_string())),
             }
         }
 
-        pub fn save_history<'a>(&mut self, fname: &str) -> Result<(), LKErr<'a>> {
+        pub fn save_history(&mut self, fname: &str) -> Result<(), LKErr> {
+            if !self.history {
+                return Ok(());
+            }
             match self.editor.save_history(&fname) {
                 Ok(_) => Ok(()),
                 Err(ReadlineError::Eof | ReadlineError::Interrupted) => Err(LKErr::EOF),
-                Err(_) => Err(LKErr::Error("failed to write history file")),
+                Err(_) => Err(LKErr::Io("failed to write history file".to_string())),
             }
         }
 
-        pub fn readline<'a>(&mut self, prompt: &str) -> Result<String, LKErr<'a>> {
-            match self.editor.readline(&prompt) {
+        pub fn readline(&mut self, prompt: &str) -> Result<String, LKErr> {
+            match self.editor.readline(prompt) {
                 Ok(line) => Ok(line),
-                Err(_) => Err(LKErr::Error("failed to read from input")),
+                Err(_) => Err(LKErr::Error("failed to read from input".to_string())),
             }
         }
     }
@@ -171,6 +271,10 @@ pub mod editor {
             Self { history: vec![] }
         }
 
+        pub fn set_state(&mut self, _state: crate::lk::LKRef) {}
+
+        pub fn set_history(&mut self, _enabled: bool) {}
+
         pub fn clear_history(&mut self) {
             self.history.clear();
         }
@@ -179,15 +283,15 @@ pub mod editor {
             self.history.push(entry.to_string());
         }
 
-        pub fn load_history<'a>(&mut self, _fname: &str) -> Result<(), LKErr<'a>> {
+        pub fn load_history(&mut self, _fname: &str) -> Result<(), LKErr> {
             Ok(())
         }
 
-        pub fn save_history<'a>(&mut self, _fname: &str) -> Result<(), LKErr<'a>> {
+        pub fn save_history(&mut self, _fname: &str) -> Result<(), LKErr> {
             Ok(())
         }
 
-        pub fn readline<'a>(&mut self, _prompt: &str) -> Result<String, LKErr<'a>> {
+        pub fn readline(&mut self, _prompt: &str) -> Result<String, LKErr> {
             Ok("".to_string())
         }
     }
@@ -227,30 +331,118 @@ pub fn call_cmd_with_input(cmd: &str, args: &Vec<String>, input: &str) -> io::Re
 
 pub fn get_cmd_args_from_command(command: &str) -> io::Result<(String, Vec<String>)> {
     let args = match split(command) {
-        Some(c) => c,
-        None => {
+        Some(c) if !c.is_empty() => c,
+        _ => {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("Failed to parse the command: {:?}", command),
             ))
         }
     };
-    Ok((shellexpand::full(&args[0]).unwrap().into_owned(), args[1..].to_vec()))
+    let cmd = shellexpand::full(&args[0]).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    Ok((cmd.into_owned(), args[1..].to_vec()))
+}
+
+/// The closest candidate within a small edit distance, for "did you mean" hints.
+pub fn did_you_mean<'a>(word: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .filter(|c| *c != word)
+        .map(|c| (strsim::damerau_levenshtein(word, c), c))
+        .filter(|(d, _)| *d <= 2 && *d < word.chars().count())
+        .min()
+        .map(|(_, c)| c)
+}
+
+pub fn normalize_name(name: &str) -> String {
+    name.nfc().collect()
+}
+
+pub fn quote_name(name: &str) -> String {
+    let needs_quotes = name.is_empty()
+        || name.starts_with('"')
+        || name.chars().any(|c| c.is_whitespace() || c == '\u{200B}' || c == ';');
+    if needs_quotes {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.to_string()
+    }
+}
+
+/// Quote free text like a comment if it would otherwise be cut at `;`, `|` or `>`. The leading
+/// backslash tells these quotes apart from a comment that starts with a quote itself.
+pub fn quote_text(text: &str) -> String {
+    let needs_quotes = text.starts_with(['"', '\\', '|', '>'])
+        || text.contains(';')
+        || text.split_whitespace().skip(1).any(|w| w.starts_with(['|', '>']));
+    if needs_quotes {
+        format!("\\\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn unquote_name(name: &str) -> String {
+    if !(name.len() >= 2 && name.starts_with('"') && name.ends_with('"')) {
+        return name.to_string();
+    }
+    let mut result = String::new();
+    let mut chars = name[1..name.len() - 1].chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some('"' | '\\')) => result.push(chars.next().unwrap()),
+            _ => result.push(c),
+        }
+    }
+    result
 }
 
-pub fn get_copy_command_from_env() -> (String, Vec<String>) {
-    let cmd_os_str = env::var_os("HEL_PB").unwrap_or_else(|| match env::consts::OS {
-        _ if env::var("TMUX").is_ok() => OsString::from("tmux load-buffer -"),
-        "macos" => OsString::from("pbcopy"),
-        "linux" => OsString::from("xclip"),
-        _ => OsString::from("cat"),
-    });
-    get_cmd_args_from_command(&cmd_os_str.to_string_lossy()).unwrap_or_else(|_| ("cat".to_string(), vec![]))
+/// Join command line arguments into one hel command, quoting the arguments the shell already split
+/// off, so `hel add "my bank" R 99 now` and `hel edit x "comment=a b"` keep their words together.
+pub fn command_line(args: &[String]) -> String {
+    let mut words = vec![];
+    for (i, arg) in args.iter().enumerate() {
+        let word = match arg.split_once('=') {
+            _ if i == 0 => arg.to_string(),
+            Some((field, "")) => format!("{}=", field),
+            Some((field, value)) if !field.is_empty() && field.chars().all(|c| c.is_ascii_lowercase()) => {
+                format!("{}={}", field, quote_name(value))
+            }
+            _ => quote_name(arg),
+        };
+        words.push(word);
+    }
+    words.join(" ")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parser::command_parser;
+    use crate::structs::Command;
+
+    #[test]
+    fn command_line_test() {
+        let line = |args: &[&str]| command_line(&args.iter().map(|a| a.to_string()).collect::<Vec<String>>());
+        assert_eq!(line(&["enc", "github"]), "enc github");
+        assert_eq!(line(&["add", "my bank", "R", "99", "now"]), "add \"my bank\" R 99 now");
+        assert_eq!(line(&["edit", "x", "comment=a b", "seq="]), "edit x comment=\"a b\" seq=");
+        assert_eq!(line(&["ls"]), "ls");
+    }
+
+    #[test]
+    fn editor_history_test() {
+        let file = std::env::temp_dir().join(format!("hel_history_test_{}", std::process::id()));
+        let fname = file.to_str().unwrap();
+        let rl = editor::Editor::new();
+        rl.lock().set_history(false);
+        rl.lock().add_history_entry("ls");
+        assert_eq!(rl.lock().save_history(fname), Ok(()));
+        assert!(!file.exists());
+        rl.lock().set_history(true);
+        assert_eq!(rl.lock().save_history(fname), Ok(()));
+        assert!(file.exists());
+        std::fs::remove_file(&file).unwrap();
+    }
 
     #[test]
     fn cmd_exec_test() {
@@ -283,4 +475,19 @@ line 4"###
         let output = call_cmd_with_input(cmd, &args, input).unwrap();
         assert_eq!(output, "Hello World!");
     }
+
+    #[test]
+    fn quote_name_test() {
+        assert_eq!(quote_name("ableton89"), "ableton89");
+        assert_eq!(quote_name("Grüße"), "Grüße");
+        assert_eq!(quote_name("my bank"), "\"my bank\"");
+        assert_eq!(quote_name("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_name(""), "\"\"");
+        for name in ["my bank", "say \"hi\"", "back\\slash here", "plain", "", "a;b"] {
+            assert_eq!(unquote_name(&quote_name(name)), name);
+            let rm = command_parser::cmd(&format!("rm {}", quote_name(name)));
+            assert_eq!(rm, Ok(Command::Rm(name.to_string())));
+        }
+        assert_eq!(normalize_name("cafe\u{301}"), "caf\u{e9}");
+    }
 }
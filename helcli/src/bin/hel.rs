@@ -1,12 +1,100 @@
 extern crate hel;
 
-use hel::structs::init;
+use hel::structs::{init, CONFIG};
+use hel::utils::command_line;
+use std::io::BufRead;
 
 pub fn main() {
-    let mut lkread = match init() { Some(r) => r, None => { return; } };
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(errors) = CONFIG.check() {
+        for err in errors.iter() {
+            eprintln!("{}", err);
+        }
+        std::process::exit(errors[0].exit_code());
+    }
+    let (mut json, mut batch, mut strict) = (false, false, false);
+
+    while let Some(flag) = args.first().filter(|a| a.starts_with("--") && *a != "--help") {
+        match flag.as_str() {
+            "--json" => json = true,
+            "--batch" => batch = true,
+            "--strict" => strict = true,
+            _ => {
+                eprintln!("error: unknown option {}, use --json, --batch, --strict or --help", flag);
+                std::process::exit(2);
+            }
+        }
+        args.remove(0);
+    }
+    if strict && !batch {
+        eprintln!("error: --strict only works together with --batch");
+        std::process::exit(2);
+    }
+    if batch && !args.is_empty() {
+        eprintln!("error: --batch reads the commands from stdin and takes no arguments");
+        std::process::exit(2);
+    }
+    // the init file already runs through the history, so batch mode has to turn it off here
+    let mut lkread = match init(&CONFIG, !batch) {
+        Some(r) => r,
+        None => {
+            return;
+        }
+    };
+    if json {
+        lkread.state.lock().borrow_mut().settings.output = "json".to_string();
+    }
+
+    // hel --batch runs one command per line from stdin and prints one record per command,
+    // the history file is left alone and --strict stops at the first failing command
+    if batch {
+        let mut code = 0;
+        for line in std::io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    eprintln!("error: failed to read from stdin: {}", err);
+                    std::process::exit(6);
+                }
+            };
+            lkread.input = Some(line);
+            let mut lkprint = lkread.read().eval();
+            let quit = !lkprint.print();
+            if lkprint.error.is_some() {
+                code = lkprint.exit_code();
+                if strict {
+                    break;
+                }
+            }
+            if quit {
+                break;
+            }
+        }
+        std::process::exit(code);
+    }
+
+    // hel COMMAND ARGS... runs one command after the init file, results go to stdout and
+    // messages to stderr, so it can be used from scripts
+    if !args.is_empty() {
+        lkread.input = Some(match args[0].as_str() {
+            "-h" | "--help" => "help".to_string(),
+            _ => command_line(&args),
+        });
+        let mut lkprint = lkread.read().eval();
+        lkprint.print();
+        std::process::exit(lkprint.exit_code());
+    }
 
-    while lkread.read().eval().print() {
+    // earlier typos do not count at the prompt, only a failing last command sets the exit status
+    let mut code;
+    loop {
+        let mut lkprint = lkread.read().eval();
+        code = lkprint.exit_code();
+        if !lkprint.print() {
+            break;
+        }
         lkread.refresh();
     }
     lkread.quit();
+    std::process::exit(code);
 }